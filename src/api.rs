@@ -1,7 +1,7 @@
 use base64::{engine::general_purpose::STANDARD as b64, Engine};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-fn b64arrayencode<S: Serializer>(data: &Vec<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+fn b64arrayencode<S: Serializer>(data: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
     let encoded: Vec<String> = data.iter().map(|v| b64.encode(v)).collect();
     encoded.serialize(serializer)
 }
@@ -10,16 +10,16 @@ fn b64arraydecode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<
     let encoded: Vec<String> = Deserialize::deserialize(deserializer)?;
     let decoded: Result<Vec<Vec<u8>>, base64::DecodeError> =
         encoded.iter().map(|s| b64.decode(s)).collect();
-    decoded.map_err(|e| serde::de::Error::custom(e))
+    decoded.map_err(serde::de::Error::custom)
 }
 
-fn b64encode<S: Serializer>(data: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+pub(crate) fn b64encode<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
     b64.encode(data).serialize(serializer)
 }
 
-fn b64decode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+pub(crate) fn b64decode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
     let data: String = Deserialize::deserialize(deserializer)?;
-    b64.decode(data).map_err(|e| serde::de::Error::custom(e))
+    b64.decode(data).map_err(serde::de::Error::custom)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,6 +40,15 @@ pub struct AddChainOutput {
     pub signature: Vec<u8>,
 }
 
+impl AddChainOutput {
+    /// Decode the SCT signature
+    pub fn digitally_signed(
+        &self,
+    ) -> Result<crate::signature::DigitallySigned, crate::signature::Error> {
+        crate::signature::DigitallySigned::from_bytes(&self.signature)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AddPreChainInput {
     #[serde(serialize_with = "b64arrayencode", deserialize_with = "b64arraydecode")]
@@ -57,3 +66,126 @@ pub struct AddPreChainOutput {
     #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
     pub signature: Vec<u8>,
 }
+
+impl AddPreChainOutput {
+    /// Decode the SCT signature
+    pub fn digitally_signed(
+        &self,
+    ) -> Result<crate::signature::DigitallySigned, crate::signature::Error> {
+        crate::signature::DigitallySigned::from_bytes(&self.signature)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GetSthOutput {
+    pub tree_size: u64,
+    pub timestamp: u64,
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub sha256_root_hash: Vec<u8>,
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub tree_head_signature: Vec<u8>,
+}
+
+impl GetSthOutput {
+    /// Decode the tree_head_signature
+    pub fn digitally_signed(
+        &self,
+    ) -> Result<crate::signature::DigitallySigned, crate::signature::Error> {
+        crate::signature::DigitallySigned::from_bytes(&self.tree_head_signature)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetSthConsistencyOutput {
+    #[serde(serialize_with = "b64arrayencode", deserialize_with = "b64arraydecode")]
+    pub consistency: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetProofByHashOutput {
+    pub leaf_index: u64,
+    #[serde(serialize_with = "b64arrayencode", deserialize_with = "b64arraydecode")]
+    pub audit_path: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetEntriesOutput {
+    pub entries: Vec<LeafEntry>,
+}
+
+// LeafEntry is a single item of the get-entries response
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeafEntry {
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub leaf_input: Vec<u8>,
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub extra_data: Vec<u8>,
+}
+
+impl LeafEntry {
+    /// Decode leaf_input as a MerkleTreeLeaf
+    pub fn leaf(&self) -> Result<crate::entry::MerkleTreeLeaf, crate::entry::Error> {
+        crate::entry::decode_leaf_input(&self.leaf_input)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetRootsOutput {
+    #[serde(serialize_with = "b64arrayencode", deserialize_with = "b64arraydecode")]
+    pub certificates: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetEntryAndProofOutput {
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub leaf_input: Vec<u8>,
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub extra_data: Vec<u8>,
+    #[serde(serialize_with = "b64arrayencode", deserialize_with = "b64arraydecode")]
+    pub audit_path: Vec<Vec<u8>>,
+}
+
+#[test]
+fn test_get_sth_output() {
+    let json = r#"{
+        "tree_size": 1234,
+        "timestamp": 1693958873214,
+        "sha256_root_hash": "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=",
+        "tree_head_signature": "BAMABgECAwQFBg=="
+    }"#;
+    let sth: GetSthOutput = serde_json::from_str(json).expect("parses");
+    assert_eq!(sth.tree_size, 1234);
+    assert_eq!(sth.timestamp, 1693958873214);
+    assert_eq!(sth.sha256_root_hash, (0u8..32).collect::<Vec<u8>>());
+    assert_eq!(sth.tree_head_signature, vec![4, 3, 0, 6, 1, 2, 3, 4, 5, 6]);
+    let signed = sth.digitally_signed().expect("decodes");
+    assert_eq!(
+        signed.algorithm().hash,
+        crate::signature::HashAlgorithm::Sha256
+    );
+    assert_eq!(signed.signature(), &[1, 2, 3, 4, 5, 6]);
+
+    let reencoded = serde_json::to_string(&sth).expect("serializes");
+    let reparsed: GetSthOutput = serde_json::from_str(&reencoded).expect("reparses");
+    assert_eq!(sth, reparsed);
+}
+
+#[test]
+fn test_get_entries_output() {
+    let json = r#"{"entries": [
+        {"leaf_input": "AAA=", "extra_data": ""},
+        {"leaf_input": "AQI=", "extra_data": "AwQF"}
+    ]}"#;
+    let entries: GetEntriesOutput = serde_json::from_str(json).expect("parses");
+    assert_eq!(entries.entries.len(), 2);
+    assert_eq!(entries.entries[0].leaf_input, vec![0, 0]);
+    assert!(entries.entries[0].extra_data.is_empty());
+    assert_eq!(entries.entries[1].leaf_input, vec![1, 2]);
+    assert_eq!(entries.entries[1].extra_data, vec![3, 4, 5]);
+}
+
+#[test]
+fn test_bad_base64() {
+    let json = r#"{"consistency": ["not base64!"]}"#;
+    assert!(serde_json::from_str::<GetSthConsistencyOutput>(json).is_err());
+}
@@ -58,7 +58,11 @@ enum Command {
 
 async fn read_certs(path: &str) -> std::io::Result<Vec<Vec<u8>>> {
     let pem = tokio::fs::read(path).await?;
-    return Ok(rustls_pemfile::certs(&mut pem.as_slice())?);
+    rustls_pemfile::certs(&mut pem.as_slice())
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap()
 }
 
 #[main]
@@ -71,33 +75,35 @@ async fn main() {
     let resp = match &cli.command {
         Command::AddChain { cert_path } => {
             let certs = read_certs(cert_path).await.expect("reading cert file");
-            match client.add_chain(certs).await {
-              Ok(acr) => Ok(serde_json::to_string_pretty(&acr).unwrap()),
-              Err(e) => Err(e),
-            }
+            client.add_chain(certs).await.map(|acr| to_json(&acr))
         }
         Command::AddPreChain { precert_path } => {
             let certs = read_certs(precert_path)
                 .await
                 .expect("reading precert file");
-            match client.add_pre_chain(certs).await {
-              Ok(apcr) => Ok(serde_json::to_string_pretty(&apcr).unwrap()),
-              Err(e) => Err(e),
-            }
-        }
-        Command::GetSTH {} => client.get_sth().await,
-        Command::GetSTHConsistency { first, second } => {
-            client.get_sth_consistency(*first, *second).await
-        }
-        Command::GetProofByHash { hash, tree_size } => {
-            client.get_proof_by_hash(hash, *tree_size).await
+            client.add_pre_chain(certs).await.map(|apcr| to_json(&apcr))
         }
-        Command::GetEntries { start, end } => client.get_entries(*start, *end).await,
-        Command::GetRoots {} => client.get_roots().await,
+        Command::GetSTH {} => client.get_sth().await.map(|sth| to_json(&sth)),
+        Command::GetSTHConsistency { first, second } => client
+            .get_sth_consistency(*first, *second)
+            .await
+            .map(|proof| to_json(&proof)),
+        Command::GetProofByHash { hash, tree_size } => client
+            .get_proof_by_hash(hash, *tree_size)
+            .await
+            .map(|proof| to_json(&proof)),
+        Command::GetEntries { start, end } => client
+            .get_entries(*start, *end)
+            .await
+            .map(|entries| to_json(&entries)),
+        Command::GetRoots {} => client.get_roots().await.map(|roots| to_json(&roots)),
         Command::GetEntryAndProof {
             leaf_index,
             tree_size,
-        } => client.get_entry_and_proof(*leaf_index, *tree_size).await,
+        } => client
+            .get_entry_and_proof(*leaf_index, *tree_size)
+            .await
+            .map(|entry| to_json(&entry)),
     };
 
     match resp {
@@ -0,0 +1,206 @@
+// Where monitors and scanners remember how far they've got in each log.
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api::GetSthOutput;
+
+/// Errors loading or saving checkpoints
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("can't access checkpoints: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("malformed checkpoints: {0}")]
+    JSON(#[from] serde_json::Error),
+}
+
+/// Checkpoint is the progress through one log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Checkpoint {
+    /// Index of the next entry to process
+    pub next_index: u64,
+    /// The last STH seen
+    pub sth: Option<GetSthOutput>,
+    /// Tree size the log has been verified to be consistent up to
+    pub verified_size: u64,
+}
+
+/// Store saves a Checkpoint for each log.
+/// Logs are named by the caller, such as by URL or base64 log ID.
+pub trait Store {
+    fn load(&self, log: &str) -> Result<Option<Checkpoint>, Error>;
+    fn save(&self, log: &str, checkpoint: &Checkpoint) -> Result<(), Error>;
+}
+
+/// MemoryStore keeps checkpoints for the life of the process
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    checkpoints: Mutex<HashMap<String, Checkpoint>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn load(&self, log: &str) -> Result<Option<Checkpoint>, Error> {
+        Ok(self.checkpoints.lock().unwrap().get(log).cloned())
+    }
+
+    fn save(&self, log: &str, checkpoint: &Checkpoint) -> Result<(), Error> {
+        self.checkpoints
+            .lock()
+            .unwrap()
+            .insert(log.to_string(), checkpoint.clone());
+        Ok(())
+    }
+}
+
+/// JsonFileStore keeps the checkpoints of every log in one JSON file.
+/// Saves write and sync a temporary file, and rename it over the old one, so
+/// the file is never left half written, even after a crash.
+/// Only one process may use a file at a time: saves from several processes
+/// can lose each other's checkpoints.
+#[derive(Debug)]
+pub struct JsonFileStore {
+    path: PathBuf,
+    // Serializes read-modify-write cycles within this process
+    lock: Mutex<()>,
+}
+
+impl JsonFileStore {
+    /// Store in path, which is created on the first save
+    pub fn new<P: Into<PathBuf>>(path: P) -> JsonFileStore {
+        JsonFileStore {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> Result<HashMap<String, Checkpoint>, Error> {
+        match std::fs::read(&self.path) {
+            Ok(json) => Ok(serde_json::from_slice(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Store for JsonFileStore {
+    fn load(&self, log: &str) -> Result<Option<Checkpoint>, Error> {
+        let _lock = self.lock.lock().unwrap();
+        Ok(self.read()?.remove(log))
+    }
+
+    fn save(&self, log: &str, checkpoint: &Checkpoint) -> Result<(), Error> {
+        let _lock = self.lock.lock().unwrap();
+        let mut checkpoints = self.read()?;
+        checkpoints.insert(log.to_string(), checkpoint.clone());
+
+        // A unique name, so saves never write to each other's temporary files
+        static SAVES: AtomicU64 = AtomicU64::new(0);
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            SAVES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = write_synced(tmp.as_ref(), &serde_json::to_vec_pretty(&checkpoints)?)
+            .and_then(|()| std::fs::rename(&tmp, &self.path));
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e.into());
+        }
+        sync_parent(&self.path)?;
+        Ok(())
+    }
+}
+
+/// write_synced writes data to a new file at path, and waits until it's on disk
+fn write_synced(path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// sync_parent waits until a rename to path is on disk.
+/// Directories can only be synced like this on Unix.
+#[cfg(unix)]
+fn sync_parent(path: &std::path::Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::File::open(dir)?.sync_all(),
+        _ => std::fs::File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &std::path::Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+fn test_store(store: &dyn Store) {
+    assert_eq!(store.load("log-a").expect("loads"), None);
+    let a = Checkpoint {
+        next_index: 10,
+        sth: Some(crate::signature::test_sth(
+            crate::signature::TEST_EC_KEY,
+            12,
+            1767225600012,
+            [12; 32],
+        )),
+        verified_size: 12,
+    };
+    store.save("log-a", &a).expect("saves");
+    store.save("log-b", &Checkpoint::default()).expect("saves");
+    assert_eq!(store.load("log-a").expect("loads"), Some(a.clone()));
+
+    let a2 = Checkpoint {
+        next_index: 12,
+        ..a
+    };
+    store.save("log-a", &a2).expect("saves");
+    assert_eq!(store.load("log-a").expect("loads"), Some(a2));
+    assert_eq!(
+        store.load("log-b").expect("loads"),
+        Some(Checkpoint::default())
+    );
+}
+
+#[test]
+fn test_memory_store() {
+    test_store(&MemoryStore::new());
+}
+
+#[test]
+fn test_json_file_store() {
+    let dir = std::env::temp_dir().join(format!("sect-checkpoint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("creates");
+    let path = dir.join("checkpoints.json");
+    let _ = std::fs::remove_file(&path);
+
+    test_store(&JsonFileStore::new(&path));
+    // Another store on the same file sees the saved checkpoints
+    let reopened = JsonFileStore::new(&path);
+    assert_eq!(
+        reopened.load("log-a").expect("loads").map(|c| c.next_index),
+        Some(12)
+    );
+    let names: Vec<_> = std::fs::read_dir(&dir)
+        .expect("lists")
+        .map(|e| e.expect("lists").file_name())
+        .collect();
+    assert_eq!(names, vec!["checkpoints.json"]);
+
+    std::fs::write(&path, b"{").expect("writes");
+    assert!(matches!(reopened.load("log-a"), Err(Error::JSON(_))));
+    std::fs::remove_dir_all(&dir).expect("removes");
+}
@@ -1,30 +1,266 @@
+use base64::{engine::general_purpose::STANDARD as b64, Engine};
+use futures::{Stream, StreamExt, TryStreamExt};
 use thiserror::Error;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// CT is a client for one log.
+/// Clones share the HTTP connection pool and rate limiter.
+#[derive(Clone)]
 pub struct CT {
     base_url: reqwest::Url,
     client: reqwest::Client,
+    public_key: Option<crate::signature::PublicKey>,
+    retry_policy: crate::retry::RetryPolicy,
+    rate_limiter: crate::ratelimit::RateLimiter,
 }
 
 #[derive(Error, Debug)]
 pub enum Error {
+    /// The request couldn't be sent, or the response couldn't be read
     #[error(transparent)]
+    Transport(#[from] reqwest::Error),
+
+    /// The log didn't return HTTP success. body is the log's explanation,
+    /// e.g. why add-chain was refused, cut short at MAX_ERROR_BODY bytes.
+    #[error("CT returned {status}: {body}")]
+    HTTPStatus {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+
+    #[error("malformed JSON response: {0}")]
+    JSON(#[from] serde_json::Error),
+
+    #[error("malformed log entry: {0}")]
+    TLS(crate::entry::Error),
+
+    #[error("malformed STH root hash: {0}")]
+    RootHash(crate::merkle::ProofError),
+
+    #[error("inclusion proof failed: {0}")]
+    InclusionProof(crate::merkle::ProofError),
+
+    #[error("log is not append-only: {0}")]
+    NotAppendOnly(crate::merkle::ProofError),
+
+    #[error("signature verification failed: {0}")]
+    Signature(#[from] crate::signature::Error),
+
+    #[error("invalid chain: {0}")]
+    Chain(crate::entry::Error),
+
+    #[error("SCT verification failed: {0}")]
+    SCT(#[from] crate::sct::Error),
+
+    #[error("log returned no entries starting at {0}")]
+    NoEntries(u64),
+}
+
+impl Error {
+    /// The HTTP status the log returned, if the error is one
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        match self {
+            Error::HTTPStatus { status, .. } => Some(*status),
+            Error::Transport(e) => e.status(),
+            _ => None,
+        }
+    }
+}
+
+/// Entry is a decoded log entry, as streamed by CT::entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub index: u64,
+    pub leaf: crate::entry::MerkleTreeLeaf,
+    pub extra_data: Vec<u8>,
+}
+
+/// Errors building a CT client
+#[derive(Error, Debug)]
+pub enum BuildError {
+    #[error("invalid server URL: {0}")]
+    URL(#[from] url::ParseError),
+
+    #[error("can't build HTTP client: {0}")]
     HTTP(#[from] reqwest::Error),
+}
+
+/// CTBuilder configures a CT client.
+/// HTTP options are ignored if a pre-built HTTP client is given.
+pub struct CTBuilder {
+    server: String,
+    client: Option<reqwest::Client>,
+    #[cfg(feature = "blocking")]
+    blocking_client: Option<reqwest::blocking::Client>,
+    http: HTTPOptions,
+    public_key: Option<crate::signature::PublicKey>,
+    retry_policy: crate::retry::RetryPolicy,
+    rate_limiter: crate::ratelimit::RateLimiter,
+}
+
+/// HTTPOptions are the options for building either kind of reqwest client
+#[derive(Default)]
+struct HTTPOptions {
+    timeout: Option<std::time::Duration>,
+    connect_timeout: Option<std::time::Duration>,
+    user_agent: Option<String>,
+    proxies: Vec<reqwest::Proxy>,
+    root_certificates: Vec<reqwest::Certificate>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<std::time::Duration>,
+}
+
+/// apply_http_options sets options on a reqwest ClientBuilder, either async
+/// or blocking, which have the same methods
+macro_rules! apply_http_options {
+    ($builder:expr, $options:expr) => {{
+        let options = $options;
+        let mut builder = $builder;
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(user_agent) = options.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        for proxy in options.proxies {
+            builder = builder.proxy(proxy);
+        }
+        for cert in options.root_certificates {
+            builder = builder.add_root_certificate(cert);
+        }
+        if let Some(max) = options.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = options.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        builder
+    }};
+}
+#[cfg(feature = "blocking")]
+use apply_http_options;
+
+impl CTBuilder {
+    /// Builder for a client for server, which is parsed as in CT::new
+    pub fn new(server: &str) -> CTBuilder {
+        CTBuilder {
+            server: server.to_string(),
+            client: None,
+            #[cfg(feature = "blocking")]
+            blocking_client: None,
+            http: HTTPOptions::default(),
+            public_key: None,
+            retry_policy: crate::retry::RetryPolicy::none(),
+            rate_limiter: crate::ratelimit::RateLimiter::new(),
+        }
+    }
+
+    /// Verify responses against the log's public key, as in CT::with_public_key
+    pub fn public_key(mut self, public_key: crate::signature::PublicKey) -> CTBuilder {
+        self.public_key = Some(public_key);
+        self
+    }
+
+    /// Retry failed requests according to policy
+    pub fn retry_policy(mut self, policy: crate::retry::RetryPolicy) -> CTBuilder {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Limit requests with rate_limiter
+    pub fn rate_limiter(mut self, rate_limiter: crate::ratelimit::RateLimiter) -> CTBuilder {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Use a pre-built HTTP client
+    pub fn client(mut self, client: reqwest::Client) -> CTBuilder {
+        self.client = Some(client);
+        self
+    }
+
+    /// Timeout for a whole request, from connecting to reading the body
+    pub fn timeout(mut self, timeout: std::time::Duration) -> CTBuilder {
+        self.http.timeout = Some(timeout);
+        self
+    }
 
-    #[error("CT didn't return HTTP success: {0}")]
-    HTTPStatus(reqwest::StatusCode),
+    /// Timeout for connecting to the log
+    pub fn connect_timeout(mut self, timeout: std::time::Duration) -> CTBuilder {
+        self.http.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// User-Agent header sent with every request
+    pub fn user_agent(mut self, user_agent: &str) -> CTBuilder {
+        self.http.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Send requests through proxy
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> CTBuilder {
+        self.http.proxies.push(proxy);
+        self
+    }
+
+    /// Trust cert as a root for the log's TLS certificate, as well as the
+    /// built-in roots
+    pub fn add_root_certificate(mut self, cert: reqwest::Certificate) -> CTBuilder {
+        self.http.root_certificates.push(cert);
+        self
+    }
+
+    /// Maximum idle connections kept open to the log
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> CTBuilder {
+        self.http.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long idle connections are kept open
+    pub fn pool_idle_timeout(mut self, timeout: std::time::Duration) -> CTBuilder {
+        self.http.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<CT, BuildError> {
+        let client = match self.client {
+            Some(client) => client,
+            None => apply_http_options!(reqwest::Client::builder(), self.http).build()?,
+        };
+        Ok(CT {
+            base_url: base_url(&self.server)?,
+            client,
+            public_key: self.public_key,
+            retry_policy: self.retry_policy,
+            rate_limiter: self.rate_limiter,
+        })
+    }
+}
+
+/// base_url parses the URL of a CT server, which must be able to have
+/// the endpoints' paths appended
+fn base_url(server: &str) -> Result<url::Url, url::ParseError> {
+    let base = parse_with_default_https(server)?;
+    if base.cannot_be_a_base() {
+        return Err(url::ParseError::RelativeUrlWithoutBase);
+    }
+    Ok(base)
 }
 
 /// parse_with_default_https parses a URL, including an https:// scheme unless
 /// the URL is explicitly http://
 fn parse_with_default_https(server: &str) -> Result<url::Url, url::ParseError> {
-    if !server.starts_with("http://") {
-        if !server.starts_with("https://") {
-            let mut with_default_scheme = String::from("https://");
-            with_default_scheme.push_str(server);
-            return url::Url::parse(&with_default_scheme);
-        }
+    if !server.starts_with("http://") && !server.starts_with("https://") {
+        let mut with_default_scheme = String::from("https://");
+        with_default_scheme.push_str(server);
+        return url::Url::parse(&with_default_scheme);
     }
-    return url::Url::parse(server);
+    url::Url::parse(server)
 }
 
 impl CT {
@@ -33,192 +269,508 @@ impl CT {
     /// If it should start with http://, https://, that scheme will be used.
     /// Otherwise, it will assumed to be https://.
     pub fn new(server: &str) -> Result<CT, url::ParseError> {
-        let base = parse_with_default_https(server)?;
-        if base.cannot_be_a_base() {
-            return Err(url::ParseError::RelativeUrlWithoutBase);
-        }
-        return Ok(CT {
-            base_url: base,
+        Ok(CT {
+            base_url: base_url(server)?,
             client: reqwest::Client::new(),
-        });
-    }
-
-    /// ct_url returns a URL for a CT endpoint
-    fn ct_url(&self, endpoint: &str) -> url::Url {
-        let mut url = self.base_url.clone();
-        url.path_segments_mut()
-            .ok()
-            .expect("cannot_be_a_base was checked in new()")
-            .push("ct")
-            .push("v1")
-            .push(endpoint);
-        return url;
-    }
-
-    fn get(&self, endpoint: &str) -> reqwest::RequestBuilder {
-        return self.client.get(self.ct_url(endpoint));
-    }
-
-    pub async fn add_chain(&self, chain: Vec<Vec<u8>>) -> Result<crate::api::AddChainOutput, Error> {
-        let body = crate::api::AddChainInput { chain: chain };
-        let req = self
-            .client
-            .post(self.ct_url("add-chain"))
-            .json(&body)
-            .build()
-            .expect("request");
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
-        }
-         
-        Ok(resp.json().await?)
-    }
-
-    pub async fn add_pre_chain(&self, chain: Vec<Vec<u8>>) -> Result<crate::api::AddPreChainOutput, Error> {
-        let body = crate::api::AddPreChainInput { chain: chain };
-        let req = self
-            .client
-            .post(self.ct_url("add-pre-chain"))
-            .json(&body)
-            .build()
-            .expect("request");
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
-        }
-        Ok(resp.json().await?)
+            public_key: None,
+            retry_policy: crate::retry::RetryPolicy::none(),
+            rate_limiter: crate::ratelimit::RateLimiter::new(),
+        })
     }
 
-    pub async fn get_sth(&self) -> Result<String, Error> {
-        let req = self.get_sth_request();
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
-        }
-        Ok(resp.text().await?)
+    /// New CT client for the given CT server, which verifies responses
+    /// against the log's public key.
+    /// Every STH returned by get_sth, and every SCT returned by add_chain
+    /// and add_pre_chain, has had its signature checked.
+    pub fn with_public_key(
+        server: &str,
+        public_key: crate::signature::PublicKey,
+    ) -> Result<CT, url::ParseError> {
+        let mut ct = CT::new(server)?;
+        ct.public_key = Some(public_key);
+        Ok(ct)
     }
 
-    fn get_sth_request(&self) -> reqwest::Request {
-        return self.get("get-sth").build().ok().unwrap();
+    /// Builder for a CT client with more options, like timeouts and proxies
+    pub fn builder(server: &str) -> CTBuilder {
+        CTBuilder::new(server)
     }
 
-    pub async fn get_sth_consistency(&self, first: u64, second: u64) -> Result<String, Error> {
-        let req = self.get_sth_consistency_request(first, second);
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
-        }
-        Ok(resp.text().await?)
+    /// Retry failed requests according to policy.
+    /// By default, requests aren't retried.
+    pub fn set_retry_policy(&mut self, policy: crate::retry::RetryPolicy) {
+        self.retry_policy = policy;
     }
 
-    fn get_sth_consistency_request(&self, first: u64, second: u64) -> reqwest::Request {
-        return self
-            .get("get-sth-consistency")
-            .query(&[("first", first), ("second", second)])
-            .build()
-            .ok()
-            .unwrap();
+    /// Limit requests, including retries, with rate_limiter.
+    /// By default, requests aren't limited.
+    pub fn set_rate_limiter(&mut self, rate_limiter: crate::ratelimit::RateLimiter) {
+        self.rate_limiter = rate_limiter;
     }
 
-    pub async fn get_proof_by_hash(&self, hash: &str, tree_size: u64) -> Result<String, Error> {
-        let req = self.get_proof_by_hash_request(hash, tree_size);
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
+    /// execute sends req, retrying as the retry policy allows, and fails
+    /// unless the final response is a success. The body of a final failure
+    /// is kept in Error::HTTPStatus.
+    /// Every attempt waits for the rate limiter.
+    /// safe_to_repeat is false for requests that change the log.
+    async fn execute(
+        &self,
+        req: reqwest::Request,
+        safe_to_repeat: bool,
+    ) -> Result<reqwest::Response, Error> {
+        let mut attempt = 1;
+        loop {
+            let this_attempt = req.try_clone().expect("request bodies are buffered");
+            self.rate_limiter.acquire(!safe_to_repeat).await;
+            let outcome = self.client.execute(this_attempt).await;
+            match next_step(&self.retry_policy, attempt, safe_to_repeat, outcome) {
+                Step::Done(resp) => return Ok(resp),
+                Step::Retry(delay) => tokio::time::sleep(delay).await,
+                Step::FailStatus(resp) => {
+                    let status = resp.status();
+                    let body = read_error_body(resp).await;
+                    return Err(Error::HTTPStatus { status, body });
+                }
+                Step::Fail(e) => return Err(e.into()),
+            }
+            attempt += 1;
         }
-        Ok(resp.text().await?)
     }
 
-    fn get_proof_by_hash_request(&self, hash: &str, tree_size: u64) -> reqwest::Request {
-        return self
-            .get("get-proof-by-hash")
-            .query(&[("hash", hash)])
-            .query(&[("tree_size", tree_size)])
-            .build()
-            .ok()
-            .unwrap();
+    /// get fetches url and parses the JSON response
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: url::Url) -> Result<T, Error> {
+        let req = self.client.get(url).build()?;
+        let resp = self.execute(req, true).await?;
+        parse_json(&resp.bytes().await?)
+    }
+
+    /// post submits body to url and parses the JSON response
+    async fn post<B: serde::Serialize, T: serde::de::DeserializeOwned>(
+        &self,
+        url: url::Url,
+        body: &B,
+    ) -> Result<T, Error> {
+        let req = self.client.post(url).json(body).build()?;
+        let resp = self.execute(req, false).await?;
+        parse_json(&resp.bytes().await?)
     }
 
-    pub async fn get_entries(&self, start: u64, end: u64) -> Result<String, Error> {
-        let req = self.get_entries_request(start, end);
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
+    /// add_chain submits a certificate chain, starting with the leaf.
+    /// If the client has the log's public key, the returned SCT is verified.
+    pub async fn add_chain(
+        &self,
+        chain: Vec<Vec<u8>>,
+    ) -> Result<crate::api::AddChainOutput, Error> {
+        let log_entry = match &self.public_key {
+            Some(_) => Some(crate::entry::LogEntry::from_chain(&chain).map_err(Error::Chain)?),
+            None => None,
+        };
+        let body = crate::api::AddChainInput { chain };
+        let sct: crate::api::AddChainOutput = self
+            .post(ct_url(&self.base_url, "add-chain"), &body)
+            .await?;
+        if let (Some(public_key), Some(log_entry)) = (&self.public_key, &log_entry) {
+            sct.verify(public_key, log_entry)?;
         }
-        Ok(resp.text().await?)
+        Ok(sct)
     }
 
-    fn get_entries_request(&self, start: u64, end: u64) -> reqwest::Request {
-        return self
-            .get("get-entries")
-            .query(&[("start", start), ("end", end)])
-            .build()
-            .ok()
-            .unwrap();
+    /// add_pre_chain submits a precertificate chain, starting with the precertificate.
+    /// The chain is checked with crate::precert::prepare first, so a malformed
+    /// chain fails with Error::Chain without contacting the log.
+    /// If the client has the log's public key, the returned SCT is verified.
+    pub async fn add_pre_chain(
+        &self,
+        chain: Vec<Vec<u8>>,
+    ) -> Result<crate::api::AddPreChainOutput, Error> {
+        let log_entry = crate::entry::LogEntry::from_pre_chain(&chain).map_err(Error::Chain)?;
+        let body = crate::api::AddPreChainInput { chain };
+        let sct: crate::api::AddPreChainOutput = self
+            .post(ct_url(&self.base_url, "add-pre-chain"), &body)
+            .await?;
+        if let Some(public_key) = &self.public_key {
+            sct.verify(public_key, &log_entry)?;
+        }
+        Ok(sct)
     }
 
-    pub async fn get_roots(&self) -> Result<String, Error> {
-        let req = self.get_roots_request();
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
+    /// get_sth fetches the latest STH.
+    /// If the client has the log's public key, the STH signature is verified.
+    pub async fn get_sth(&self) -> Result<crate::api::GetSthOutput, Error> {
+        let sth = self.get(get_sth_url(&self.base_url)).await?;
+        if let Some(public_key) = &self.public_key {
+            public_key.verify_sth(&sth)?;
         }
-        Ok(resp.text().await?)
+        Ok(sth)
+    }
+
+    pub async fn get_sth_consistency(
+        &self,
+        first: u64,
+        second: u64,
+    ) -> Result<crate::api::GetSthConsistencyOutput, Error> {
+        self.get(get_sth_consistency_url(&self.base_url, first, second))
+            .await
+    }
+
+    pub async fn get_proof_by_hash(
+        &self,
+        hash: &str,
+        tree_size: u64,
+    ) -> Result<crate::api::GetProofByHashOutput, Error> {
+        self.get(get_proof_by_hash_url(&self.base_url, hash, tree_size))
+            .await
     }
 
-    fn get_roots_request(&self) -> reqwest::Request {
-        return self.get("get-roots").build().ok().unwrap();
+    pub async fn get_entries(
+        &self,
+        start: u64,
+        end: u64,
+    ) -> Result<crate::api::GetEntriesOutput, Error> {
+        self.get(get_entries_url(&self.base_url, start, end)).await
+    }
+
+    /// entries streams the decoded entries with indexes in range.
+    /// Logs may return fewer entries than asked for, so get-entries is
+    /// requested again from the first missing index until the range is done.
+    pub fn entries(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Stream<Item = Result<Entry, Error>> + '_ {
+        let end = range.end;
+        futures::stream::try_unfold(range.start, move |start| async move {
+            if start >= end {
+                return Ok(None);
+            }
+            let batch = self.get_entries(start, end - 1).await?;
+            let entries = decode_entries(batch, start, end)?;
+            let next = start + entries.len() as u64;
+            Ok::<_, Error>(Some((futures::stream::iter(entries), next)))
+        })
+        .try_flatten()
+        // Like the blocking iterator, end after the first error
+        .scan(false, |failed, entry| {
+            let item = (!*failed).then_some(entry);
+            *failed = matches!(item, Some(Err(_)));
+            futures::future::ready(item)
+        })
+        .boxed()
+    }
+
+    pub async fn get_roots(&self) -> Result<crate::api::GetRootsOutput, Error> {
+        self.get(get_roots_url(&self.base_url)).await
     }
 
     pub async fn get_entry_and_proof(
         &self,
         leaf_index: u64,
         tree_size: u64,
-    ) -> Result<String, Error> {
-        let req = self.get_entry_and_proof_request(leaf_index, tree_size);
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return Err(Error::HTTPStatus(resp.status()));
+    ) -> Result<crate::api::GetEntryAndProofOutput, Error> {
+        self.get(get_entry_and_proof_url(
+            &self.base_url,
+            leaf_index,
+            tree_size,
+        ))
+        .await
+    }
+
+    /// verify_inclusion fetches an audit path for leaf_hash in the tree of the
+    /// given STH, and verifies it against the STH's root hash.
+    /// Returns the index of the leaf.
+    pub async fn verify_inclusion(
+        &self,
+        leaf_hash: &crate::merkle::Hash,
+        sth: &crate::api::GetSthOutput,
+    ) -> Result<u64, Error> {
+        root_hash(sth)?;
+        let proof = self
+            .get_proof_by_hash(&b64.encode(leaf_hash), sth.tree_size)
+            .await?;
+        check_inclusion(leaf_hash, sth, &proof)
+    }
+
+    /// verify_consistency fetches a consistency proof between two STHs and
+    /// checks that new_sth extends old_sth.
+    /// A proof that fails to verify is reported as Error::NotAppendOnly.
+    pub async fn verify_consistency(
+        &self,
+        old_sth: &crate::api::GetSthOutput,
+        new_sth: &crate::api::GetSthOutput,
+    ) -> Result<(), Error> {
+        root_hash(old_sth)?;
+        root_hash(new_sth)?;
+        let proof = if needs_consistency_proof(old_sth, new_sth) {
+            self.get_sth_consistency(old_sth.tree_size, new_sth.tree_size)
+                .await?
+                .consistency
+        } else {
+            vec![]
+        };
+        check_consistency(old_sth, new_sth, &proof)
+    }
+}
+
+// The rest is shared by the async and blocking clients
+
+/// ct_url returns a URL for a CT endpoint
+fn ct_url(base: &url::Url, endpoint: &str) -> url::Url {
+    let mut url = base.clone();
+    url.path_segments_mut()
+        .expect("cannot_be_a_base was checked in new()")
+        .push("ct")
+        .push("v1")
+        .push(endpoint);
+    url
+}
+
+fn get_sth_url(base: &url::Url) -> url::Url {
+    ct_url(base, "get-sth")
+}
+
+fn get_sth_consistency_url(base: &url::Url, first: u64, second: u64) -> url::Url {
+    let mut url = ct_url(base, "get-sth-consistency");
+    url.query_pairs_mut()
+        .append_pair("first", &first.to_string())
+        .append_pair("second", &second.to_string());
+    url
+}
+
+fn get_proof_by_hash_url(base: &url::Url, hash: &str, tree_size: u64) -> url::Url {
+    let mut url = ct_url(base, "get-proof-by-hash");
+    url.query_pairs_mut()
+        .append_pair("hash", hash)
+        .append_pair("tree_size", &tree_size.to_string());
+    url
+}
+
+fn get_entries_url(base: &url::Url, start: u64, end: u64) -> url::Url {
+    let mut url = ct_url(base, "get-entries");
+    url.query_pairs_mut()
+        .append_pair("start", &start.to_string())
+        .append_pair("end", &end.to_string());
+    url
+}
+
+fn get_roots_url(base: &url::Url) -> url::Url {
+    ct_url(base, "get-roots")
+}
+
+fn get_entry_and_proof_url(base: &url::Url, leaf_index: u64, tree_size: u64) -> url::Url {
+    let mut url = ct_url(base, "get-entry-and-proof");
+    url.query_pairs_mut()
+        .append_pair("leaf_index", &leaf_index.to_string())
+        .append_pair("tree_size", &tree_size.to_string());
+    url
+}
+
+/// HTTPResponse is what the async and blocking clients' responses have in
+/// common
+trait HTTPResponse {
+    fn status(&self) -> reqwest::StatusCode;
+    fn headers(&self) -> &reqwest::header::HeaderMap;
+}
+
+impl HTTPResponse for reqwest::Response {
+    fn status(&self) -> reqwest::StatusCode {
+        self.status()
+    }
+
+    fn headers(&self) -> &reqwest::header::HeaderMap {
+        self.headers()
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl HTTPResponse for reqwest::blocking::Response {
+    fn status(&self) -> reqwest::StatusCode {
+        self.status()
+    }
+
+    fn headers(&self) -> &reqwest::header::HeaderMap {
+        self.headers()
+    }
+}
+
+/// Step is what to do after an attempt at a request
+enum Step<R> {
+    /// Return the successful response
+    Done(R),
+    /// Wait, then try again
+    Retry(std::time::Duration),
+    /// Give up, with the log's status and error body
+    FailStatus(R),
+    /// Give up, after failing to talk to the log
+    Fail(reqwest::Error),
+}
+
+/// next_step decides what to do after the given attempt, starting from 1,
+/// according to policy
+fn next_step<R: HTTPResponse>(
+    policy: &crate::retry::RetryPolicy,
+    attempt: u32,
+    safe_to_repeat: bool,
+    outcome: Result<R, reqwest::Error>,
+) -> Step<R> {
+    let resp = match outcome {
+        Ok(resp) if resp.status().is_success() => return Step::Done(resp),
+        Ok(resp) => resp,
+        Err(e) => {
+            let retry = policy.retries_error(&e, safe_to_repeat);
+            return match policy.next_delay(attempt, retry, None) {
+                Some(delay) => Step::Retry(delay),
+                None => Step::Fail(e),
+            };
+        }
+    };
+    let retry = policy.retries_status(resp.status().as_u16(), safe_to_repeat);
+    match policy.next_delay(attempt, retry, crate::retry::retry_after(resp.headers())) {
+        Some(delay) => Step::Retry(delay),
+        None => Step::FailStatus(resp),
+    }
+}
+
+/// How much of an error response is kept in Error::HTTPStatus
+pub const MAX_ERROR_BODY: usize = 4096;
+
+/// read_error_body reads the start of an error response
+async fn read_error_body(mut resp: reqwest::Response) -> String {
+    let mut body = Vec::new();
+    let read = loop {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                body.extend_from_slice(&chunk);
+                if body.len() > MAX_ERROR_BODY {
+                    break Ok(());
+                }
+            }
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
         }
-        Ok(resp.text().await?)
+    };
+    error_body(body, read)
+}
+
+/// error_body is the text of the error response in body, marking it if it
+/// was cut short or couldn't be read to the end
+fn error_body<E: std::fmt::Display>(mut body: Vec<u8>, read: Result<(), E>) -> String {
+    let truncated = body.len() > MAX_ERROR_BODY;
+    body.truncate(MAX_ERROR_BODY);
+    let mut text = String::from_utf8_lossy(&body).into_owned();
+    if truncated {
+        text.push_str(" [truncated]");
     }
+    if let Err(e) = read {
+        text.push_str(&format!(" [unreadable body: {e}]"));
+    }
+    text
+}
+
+/// parse_json parses a response body, reporting bad JSON as Error::JSON
+fn parse_json<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    Ok(serde_json::from_slice(body)?)
+}
 
-    fn get_entry_and_proof_request(&self, leaf_index: u64, tree_size: u64) -> reqwest::Request {
-        return self
-            .get("get-entry-and-proof")
-            .query(&[("leaf_index", leaf_index), ("tree_size", tree_size)])
-            .build()
-            .ok()
-            .unwrap();
+/// decode_entries decodes a get-entries response for a request starting at
+/// start, ignoring anything at or past end
+fn decode_entries(
+    batch: crate::api::GetEntriesOutput,
+    start: u64,
+    end: u64,
+) -> Result<Vec<Result<Entry, Error>>, Error> {
+    if batch.entries.is_empty() {
+        return Err(Error::NoEntries(start));
     }
+    let wanted = (end - start).min(batch.entries.len() as u64);
+    Ok(batch
+        .entries
+        .into_iter()
+        .take(wanted as usize)
+        .zip(start..)
+        .map(|(entry, index)| {
+            Ok(Entry {
+                index,
+                leaf: entry.leaf().map_err(Error::TLS)?,
+                extra_data: entry.extra_data,
+            })
+        })
+        .collect())
+}
+
+/// root_hash decodes the STH's root hash
+fn root_hash(sth: &crate::api::GetSthOutput) -> Result<crate::merkle::Hash, Error> {
+    crate::merkle::to_hash(&sth.sha256_root_hash).map_err(Error::RootHash)
+}
+
+/// check_inclusion verifies proof against the STH's root hash
+fn check_inclusion(
+    leaf_hash: &crate::merkle::Hash,
+    sth: &crate::api::GetSthOutput,
+    proof: &crate::api::GetProofByHashOutput,
+) -> Result<u64, Error> {
+    let root = root_hash(sth)?;
+    crate::merkle::verify_inclusion(
+        leaf_hash,
+        proof.leaf_index,
+        sth.tree_size,
+        &proof.audit_path,
+        &root,
+    )
+    .map_err(Error::InclusionProof)?;
+    Ok(proof.leaf_index)
+}
+
+/// needs_consistency_proof is false when no proof is needed, or possible
+fn needs_consistency_proof(
+    old_sth: &crate::api::GetSthOutput,
+    new_sth: &crate::api::GetSthOutput,
+) -> bool {
+    old_sth.tree_size != 0 && old_sth.tree_size < new_sth.tree_size
+}
+
+/// check_consistency verifies that proof shows new_sth extends old_sth
+fn check_consistency(
+    old_sth: &crate::api::GetSthOutput,
+    new_sth: &crate::api::GetSthOutput,
+    proof: &[Vec<u8>],
+) -> Result<(), Error> {
+    let old_root = root_hash(old_sth)?;
+    let new_root = root_hash(new_sth)?;
+    crate::merkle::verify_consistency(
+        old_sth.tree_size,
+        &old_root,
+        new_sth.tree_size,
+        &new_root,
+        proof,
+    )
+    .map_err(Error::NotAppendOnly)
 }
 
 #[test]
 fn test_request_urls() {
     let ct = CT::new("server/prefix").ok().unwrap();
+    let base = &ct.base_url;
     assert_eq!(
-        ct.get_sth_request().url().as_str(),
+        get_sth_url(base).as_str(),
         "https://server/prefix/ct/v1/get-sth"
     );
     assert_eq!(
-        ct.get_sth_consistency_request(1234, 99999).url().as_str(),
+        get_sth_consistency_url(base, 1234, 99999).as_str(),
         "https://server/prefix/ct/v1/get-sth-consistency?first=1234&second=99999"
     );
     assert_eq!(
-        ct.get_proof_by_hash_request("some-hash", 1).url().as_str(),
+        get_proof_by_hash_url(base, "some-hash", 1).as_str(),
         "https://server/prefix/ct/v1/get-proof-by-hash?hash=some-hash&tree_size=1"
     );
     assert_eq!(
-        ct.get_entries_request(9000, 9255).url().as_str(),
+        get_entries_url(base, 9000, 9255).as_str(),
         "https://server/prefix/ct/v1/get-entries?start=9000&end=9255"
     );
     assert_eq!(
-        ct.get_roots_request().url().as_str(),
+        get_roots_url(base).as_str(),
         "https://server/prefix/ct/v1/get-roots"
     );
     assert_eq!(
-        ct.get_entry_and_proof_request(777, 7777).url().as_str(),
+        get_entry_and_proof_url(base, 777, 7777).as_str(),
         "https://server/prefix/ct/v1/get-entry-and-proof?leaf_index=777&tree_size=7777"
     );
 }
@@ -239,13 +791,272 @@ fn test_server_args() {
     ]
     .iter()
     {
+        let ct = CT::new(server).expect("parses");
+        assert_eq!(get_sth_url(&ct.base_url).as_str(), *url);
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_builder() {
+    use crate::testlog::{serve, Response};
+
+    let url = serve(|request| {
+        assert_eq!(request.headers["user-agent"], "sect-test/1.0");
+        Response::sth(&crate::testlog::sth(3))
+    })
+    .await;
+    let key = crate::signature::PublicKey::from_pem(crate::signature::TEST_EC_PUB.as_bytes())
+        .expect("parses");
+    let ct = CT::builder(&url)
+        .user_agent("sect-test/1.0")
+        .timeout(std::time::Duration::from_secs(10))
+        .pool_max_idle_per_host(1)
+        .public_key(key)
+        .build()
+        .expect("builds");
+    assert_eq!(ct.get_sth().await.expect("fetches").tree_size, 3);
+
+    // A pre-built client is used as is
+    let client = reqwest::Client::builder()
+        .user_agent("sect-test/1.0")
+        .build()
+        .expect("builds");
+    let ct = CT::builder(&url).client(client).build().expect("builds");
+    assert_eq!(ct.get_sth().await.expect("fetches").tree_size, 3);
+
+    // The server is parsed as in CT::new
+    let ct = CT::builder("server/prefix").build().expect("builds");
+    assert_eq!(
+        get_sth_url(&ct.base_url).as_str(),
+        "https://server/prefix/ct/v1/get-sth"
+    );
+    assert!(matches!(
+        CT::builder("https://[::1").build(),
+        Err(BuildError::URL(_))
+    ));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_entries() {
+    use crate::testlog::{get_entries, leaf_input, serve, Response};
+    use std::sync::{Arc, Mutex};
+
+    let requests = Arc::new(Mutex::new(vec![]));
+    let seen = requests.clone();
+    let url = serve(move |request| {
+        assert_eq!(request.path, "/ct/v1/get-entries");
+        seen.lock()
+            .unwrap()
+            .push((request.param("start"), request.param("end")));
+        get_entries(request, 10, 3)
+    })
+    .await;
+    let ct = CT::new(&url).expect("parses");
+
+    let entries: Vec<Entry> = ct.entries(2..9).try_collect().await.expect("streams");
+    assert_eq!(
+        entries.iter().map(|e| e.index).collect::<Vec<_>>(),
+        (2..9).collect::<Vec<_>>()
+    );
+    for entry in &entries {
         assert_eq!(
-            CT::new(server)
-                .expect("parses")
-                .get_sth_request()
-                .url()
-                .as_str(),
-            *url
+            entry.leaf.to_bytes().expect("encodes"),
+            leaf_input(entry.index)
         );
+        assert_eq!(entry.leaf.timestamp(), entry.index);
     }
+    assert_eq!(*requests.lock().unwrap(), vec![(2, 8), (5, 8), (8, 8)]);
+
+    assert!(ct.entries(5..5).next().await.is_none());
+
+    // Past the end of the log, the error is from the log
+    let err = ct
+        .entries(8..12)
+        .try_collect::<Vec<_>>()
+        .await
+        .expect_err("fails");
+    assert!(matches!(err, Error::HTTPStatus { status, .. } if status == 400));
+    assert_eq!(err.status().map(|s| s.as_u16()), Some(400));
+
+    // A log returning nothing would otherwise be asked forever
+    let url = serve(|_| Response::json(serde_json::json!({ "entries": [] }))).await;
+    let ct = CT::new(&url).expect("parses");
+    let err = ct
+        .entries(0..1)
+        .try_collect::<Vec<_>>()
+        .await
+        .expect_err("fails");
+    assert!(matches!(err, Error::NoEntries(0)));
+
+    // Nothing follows a malformed entry, even from the same batch
+    let url = serve(|request| {
+        let mut response = get_entries(request, 10, 4);
+        let mut body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        body["entries"][1]["leaf_input"] = "AAAA".into();
+        response.body = body.to_string();
+        response
+    })
+    .await;
+    let ct = CT::new(&url).expect("parses");
+    let results: Vec<_> = ct.entries(0..8).collect().await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().expect("decodes").index, 0);
+    assert!(matches!(results[1], Err(Error::TLS(_))));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_retry() {
+    use crate::testlog::{serve, Response};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // Fails with status until it has been asked fail_times times
+    async fn server(status: u16, fail_times: u32) -> (String, Arc<AtomicU32>) {
+        let count = Arc::new(AtomicU32::new(0));
+        let seen = count.clone();
+        let url = serve(move |_| {
+            if seen.fetch_add(1, Ordering::SeqCst) < fail_times {
+                let mut resp = Response::status(status, "busy");
+                resp.headers
+                    .push(("retry-after".to_string(), "0".to_string()));
+                return resp;
+            }
+            Response::sth(&crate::testlog::sth(3))
+        })
+        .await;
+        (url, count)
+    }
+    let policy = crate::retry::RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        ..crate::retry::RetryPolicy::default()
+    };
+
+    let (url, count) = server(503, 2).await;
+    let mut ct = CT::new(&url).expect("parses");
+    ct.set_retry_policy(policy.clone());
+    assert_eq!(ct.get_sth().await.expect("retries").tree_size, 3);
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+
+    // Gives up after max_attempts
+    let (url, count) = server(500, 5).await;
+    let mut ct = CT::new(&url).expect("parses");
+    ct.set_retry_policy(policy.clone());
+    let err = ct.get_sth().await.expect_err("fails");
+    assert!(matches!(err, Error::HTTPStatus { status, body } if status == 500 && body == "busy"));
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+
+    // add-chain isn't retried after a 500, as the log may have logged it
+    let chain = vec![include_bytes!("../testdata/cert.der").to_vec()];
+    let (url, count) = server(500, 1).await;
+    let mut ct = CT::new(&url).expect("parses");
+    ct.set_retry_policy(policy.clone());
+    assert!(ct.add_chain(chain.clone()).await.is_err());
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    // but is after a 429
+    let (url, count) = server(429, 1).await;
+    let mut ct = CT::new(&url).expect("parses");
+    ct.set_retry_policy(policy.clone());
+    // The test server answers with an STH, which isn't an SCT
+    assert!(matches!(ct.add_chain(chain).await, Err(Error::JSON(_))));
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+
+    // Without a policy, nothing is retried
+    let (url, count) = server(503, 1).await;
+    let ct = CT::new(&url).expect("parses");
+    assert!(ct.get_sth().await.is_err());
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_error_body() {
+    use crate::testlog::{serve, Response};
+
+    // Logs explain why a chain was refused
+    let url = serve(|_| Response::status(400, "Unknown root: no path to a trusted root")).await;
+    let ct = CT::new(&url).expect("parses");
+    let chain = vec![include_bytes!("../testdata/cert.der").to_vec()];
+    let err = ct.add_chain(chain).await.expect_err("fails");
+    assert!(matches!(
+        &err,
+        Error::HTTPStatus { status, body }
+            if *status == 400 && body == "Unknown root: no path to a trusted root"
+    ));
+    assert_eq!(
+        err.to_string(),
+        "CT returned 400 Bad Request: Unknown root: no path to a trusted root"
+    );
+
+    let url = serve(|_| Response::status(200, "not json")).await;
+    let ct = CT::new(&url).expect("parses");
+    assert!(matches!(ct.get_sth().await, Err(Error::JSON(_))));
+    assert_eq!(ct.get_sth().await.expect_err("fails").status(), None);
+
+    // Only the start of a long error is kept
+    let url = serve(|_| Response::status(500, &"x".repeat(MAX_ERROR_BODY * 4))).await;
+    let ct = CT::new(&url).expect("parses");
+    let expected = format!("{} [truncated]", "x".repeat(MAX_ERROR_BODY));
+    assert!(matches!(
+        ct.get_sth().await,
+        Err(Error::HTTPStatus { body, .. }) if body == expected
+    ));
+}
+
+#[test]
+fn test_error_body_unreadable() {
+    assert_eq!(
+        error_body(b"partial".to_vec(), Err("connection reset")),
+        "partial [unreadable body: connection reset]"
+    );
+    assert_eq!(error_body(b"whole".to_vec(), Ok::<(), String>(())), "whole");
+}
+
+#[cfg(test)]
+#[tokio::test(start_paused = true)]
+async fn test_rate_limit() {
+    use crate::ratelimit::{RateLimiter, TokenBucket};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    let url =
+        crate::testlog::serve(|_| crate::testlog::Response::sth(&crate::testlog::sth(3))).await;
+    let limiter = RateLimiter::new().reads(TokenBucket::new(20.0, 1));
+    let ct = CT::builder(&url)
+        .rate_limiter(limiter)
+        .build()
+        .expect("builds");
+    // Clones share one budget
+    let clone = ct.clone();
+    let start = Instant::now();
+    let (a, b) = futures::join!(ct.get_sth(), clone.get_sth());
+    a.expect("fetches");
+    b.expect("fetches");
+    assert_eq!(start.elapsed(), Duration::from_millis(50));
+    clone.get_sth().await.expect("fetches");
+    assert_eq!(start.elapsed(), Duration::from_millis(100));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_bad_root_hash() {
+    let ct = CT::new("server").expect("parses");
+    let old = crate::testlog::sth(3);
+    let mut new = crate::testlog::sth(5);
+    new.sha256_root_hash.pop();
+    // A malformed root hash isn't evidence of a split view
+    assert!(matches!(
+        ct.verify_consistency(&old, &new).await,
+        Err(Error::RootHash(crate::merkle::ProofError::BadHashLength(
+            31
+        )))
+    ));
+    assert!(matches!(
+        ct.verify_inclusion(&[0; 32], &new).await,
+        Err(Error::RootHash(_))
+    ));
 }
@@ -0,0 +1,352 @@
+// A synchronous client, for programs without an async runtime.
+// It mirrors the async CT, sharing its URLs, responses and verification.
+use std::io::Read;
+
+use base64::{engine::general_purpose::STANDARD as b64, Engine};
+
+use super::{
+    apply_http_options, base_url, check_consistency, check_inclusion, ct_url, decode_entries,
+    error_body, get_entries_url, get_entry_and_proof_url, get_proof_by_hash_url, get_roots_url,
+    get_sth_consistency_url, get_sth_url, needs_consistency_proof, next_step, parse_json,
+    root_hash, BuildError, CTBuilder, Entry, Error, Step, MAX_ERROR_BODY,
+};
+
+/// CT is a blocking client for one log.
+/// Clones share the HTTP connection pool and rate limiter.
+#[derive(Clone)]
+pub struct CT {
+    base_url: reqwest::Url,
+    client: reqwest::blocking::Client,
+    public_key: Option<crate::signature::PublicKey>,
+    retry_policy: crate::retry::RetryPolicy,
+    rate_limiter: crate::ratelimit::RateLimiter,
+}
+
+impl CTBuilder {
+    /// Use a pre-built HTTP client for a blocking CT client
+    pub fn blocking_client(mut self, client: reqwest::blocking::Client) -> CTBuilder {
+        self.blocking_client = Some(client);
+        self
+    }
+
+    /// build_blocking builds a blocking CT client, rather than an async one
+    pub fn build_blocking(self) -> Result<CT, BuildError> {
+        let client = match self.blocking_client {
+            Some(client) => client,
+            None => apply_http_options!(reqwest::blocking::Client::builder(), self.http).build()?,
+        };
+        Ok(CT {
+            base_url: base_url(&self.server)?,
+            client,
+            public_key: self.public_key,
+            retry_policy: self.retry_policy,
+            rate_limiter: self.rate_limiter,
+        })
+    }
+}
+
+impl CT {
+    /// New CT client for the given CT server, parsed as in the async CT::new
+    pub fn new(server: &str) -> Result<CT, BuildError> {
+        CT::builder(server).build_blocking()
+    }
+
+    /// New CT client for the given CT server, which verifies responses
+    /// against the log's public key, as in the async CT::with_public_key
+    pub fn with_public_key(
+        server: &str,
+        public_key: crate::signature::PublicKey,
+    ) -> Result<CT, BuildError> {
+        CT::builder(server).public_key(public_key).build_blocking()
+    }
+
+    /// Builder for a CT client with more options, finished with
+    /// CTBuilder::build_blocking
+    pub fn builder(server: &str) -> CTBuilder {
+        CTBuilder::new(server)
+    }
+
+    /// Retry failed requests according to policy.
+    /// By default, requests aren't retried.
+    pub fn set_retry_policy(&mut self, policy: crate::retry::RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Limit requests, including retries, with rate_limiter.
+    /// By default, requests aren't limited.
+    pub fn set_rate_limiter(&mut self, rate_limiter: crate::ratelimit::RateLimiter) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    /// execute is the async CT::execute, sleeping the thread between attempts
+    fn execute(
+        &self,
+        req: reqwest::blocking::Request,
+        safe_to_repeat: bool,
+    ) -> Result<reqwest::blocking::Response, Error> {
+        let mut attempt = 1;
+        loop {
+            let this_attempt = req.try_clone().expect("request bodies are buffered");
+            self.rate_limiter.acquire_blocking(!safe_to_repeat);
+            let outcome = self.client.execute(this_attempt);
+            match next_step(&self.retry_policy, attempt, safe_to_repeat, outcome) {
+                Step::Done(resp) => return Ok(resp),
+                Step::Retry(delay) => std::thread::sleep(delay),
+                Step::FailStatus(resp) => {
+                    let status = resp.status();
+                    let mut body = Vec::new();
+                    let read = resp
+                        .take(MAX_ERROR_BODY as u64 + 1)
+                        .read_to_end(&mut body)
+                        .map(|_| ());
+                    let body = error_body(body, read);
+                    return Err(Error::HTTPStatus { status, body });
+                }
+                Step::Fail(e) => return Err(e.into()),
+            }
+            attempt += 1;
+        }
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(&self, url: url::Url) -> Result<T, Error> {
+        let req = self.client.get(url).build()?;
+        let resp = self.execute(req, true)?;
+        parse_json(&resp.bytes()?)
+    }
+
+    fn post<B: serde::Serialize, T: serde::de::DeserializeOwned>(
+        &self,
+        url: url::Url,
+        body: &B,
+    ) -> Result<T, Error> {
+        let req = self.client.post(url).json(body).build()?;
+        let resp = self.execute(req, false)?;
+        parse_json(&resp.bytes()?)
+    }
+
+    /// add_chain submits a certificate chain, starting with the leaf.
+    /// If the client has the log's public key, the returned SCT is verified.
+    pub fn add_chain(&self, chain: Vec<Vec<u8>>) -> Result<crate::api::AddChainOutput, Error> {
+        let log_entry = match &self.public_key {
+            Some(_) => Some(crate::entry::LogEntry::from_chain(&chain).map_err(Error::Chain)?),
+            None => None,
+        };
+        let body = crate::api::AddChainInput { chain };
+        let sct: crate::api::AddChainOutput =
+            self.post(ct_url(&self.base_url, "add-chain"), &body)?;
+        if let (Some(public_key), Some(log_entry)) = (&self.public_key, &log_entry) {
+            sct.verify(public_key, log_entry)?;
+        }
+        Ok(sct)
+    }
+
+    /// add_pre_chain submits a precertificate chain, starting with the
+    /// precertificate, which is checked as in the async CT::add_pre_chain.
+    /// If the client has the log's public key, the returned SCT is verified.
+    pub fn add_pre_chain(
+        &self,
+        chain: Vec<Vec<u8>>,
+    ) -> Result<crate::api::AddPreChainOutput, Error> {
+        let log_entry = crate::entry::LogEntry::from_pre_chain(&chain).map_err(Error::Chain)?;
+        let body = crate::api::AddPreChainInput { chain };
+        let sct: crate::api::AddPreChainOutput =
+            self.post(ct_url(&self.base_url, "add-pre-chain"), &body)?;
+        if let Some(public_key) = &self.public_key {
+            sct.verify(public_key, &log_entry)?;
+        }
+        Ok(sct)
+    }
+
+    /// get_sth fetches the latest STH.
+    /// If the client has the log's public key, the STH signature is verified.
+    pub fn get_sth(&self) -> Result<crate::api::GetSthOutput, Error> {
+        let sth = self.get(get_sth_url(&self.base_url))?;
+        if let Some(public_key) = &self.public_key {
+            public_key.verify_sth(&sth)?;
+        }
+        Ok(sth)
+    }
+
+    pub fn get_sth_consistency(
+        &self,
+        first: u64,
+        second: u64,
+    ) -> Result<crate::api::GetSthConsistencyOutput, Error> {
+        self.get(get_sth_consistency_url(&self.base_url, first, second))
+    }
+
+    pub fn get_proof_by_hash(
+        &self,
+        hash: &str,
+        tree_size: u64,
+    ) -> Result<crate::api::GetProofByHashOutput, Error> {
+        self.get(get_proof_by_hash_url(&self.base_url, hash, tree_size))
+    }
+
+    pub fn get_entries(&self, start: u64, end: u64) -> Result<crate::api::GetEntriesOutput, Error> {
+        self.get(get_entries_url(&self.base_url, start, end))
+    }
+
+    /// entries iterates over the decoded entries with indexes in range,
+    /// requesting get-entries as often as the log needs
+    pub fn entries(&self, range: std::ops::Range<u64>) -> Entries<'_> {
+        Entries {
+            ct: self,
+            next: range.start,
+            end: range.end,
+            batch: vec![].into_iter(),
+            failed: false,
+        }
+    }
+
+    pub fn get_roots(&self) -> Result<crate::api::GetRootsOutput, Error> {
+        self.get(get_roots_url(&self.base_url))
+    }
+
+    pub fn get_entry_and_proof(
+        &self,
+        leaf_index: u64,
+        tree_size: u64,
+    ) -> Result<crate::api::GetEntryAndProofOutput, Error> {
+        self.get(get_entry_and_proof_url(
+            &self.base_url,
+            leaf_index,
+            tree_size,
+        ))
+    }
+
+    /// verify_inclusion fetches an audit path for leaf_hash in the tree of the
+    /// given STH, and verifies it against the STH's root hash.
+    /// Returns the index of the leaf.
+    pub fn verify_inclusion(
+        &self,
+        leaf_hash: &crate::merkle::Hash,
+        sth: &crate::api::GetSthOutput,
+    ) -> Result<u64, Error> {
+        root_hash(sth)?;
+        let proof = self.get_proof_by_hash(&b64.encode(leaf_hash), sth.tree_size)?;
+        check_inclusion(leaf_hash, sth, &proof)
+    }
+
+    /// verify_consistency fetches a consistency proof between two STHs and
+    /// checks that new_sth extends old_sth.
+    /// A proof that fails to verify is reported as Error::NotAppendOnly.
+    pub fn verify_consistency(
+        &self,
+        old_sth: &crate::api::GetSthOutput,
+        new_sth: &crate::api::GetSthOutput,
+    ) -> Result<(), Error> {
+        root_hash(old_sth)?;
+        root_hash(new_sth)?;
+        let proof = if needs_consistency_proof(old_sth, new_sth) {
+            self.get_sth_consistency(old_sth.tree_size, new_sth.tree_size)?
+                .consistency
+        } else {
+            vec![]
+        };
+        check_consistency(old_sth, new_sth, &proof)
+    }
+}
+
+/// Entries is the iterator returned by CT::entries.
+/// It ends after the first error.
+pub struct Entries<'a> {
+    ct: &'a CT,
+    next: u64,
+    end: u64,
+    batch: std::vec::IntoIter<Result<Entry, Error>>,
+    failed: bool,
+}
+
+impl Iterator for Entries<'_> {
+    type Item = Result<Entry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(entry) = self.batch.next() {
+            if entry.is_err() {
+                self.failed = true;
+                self.batch = vec![].into_iter();
+            }
+            return Some(entry);
+        }
+        if self.failed || self.next >= self.end {
+            return None;
+        }
+        let batch = self
+            .ct
+            .get_entries(self.next, self.end - 1)
+            .and_then(|batch| decode_entries(batch, self.next, self.end));
+        match batch {
+            Ok(batch) => {
+                self.next += batch.len() as u64;
+                self.batch = batch.into_iter();
+                self.next()
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_blocking() {
+    use crate::testlog::{get_entries, serve, Response};
+
+    // The test log runs on its own runtime, as the client must not be
+    // used from inside one
+    let runtime = tokio::runtime::Runtime::new().expect("starts");
+    let url = runtime.block_on(serve(|request| match request.path.as_str() {
+        "/ct/v1/get-sth" => Response::sth(&crate::testlog::sth(10)),
+        "/ct/v1/get-sth-consistency" => crate::testlog::get_sth_consistency(request),
+        "/ct/v1/get-entries" => get_entries(request, 10, 3),
+        _ => Response::status(400, "Unknown root"),
+    }));
+    let key = crate::signature::PublicKey::from_pem(crate::signature::TEST_EC_PUB.as_bytes())
+        .expect("parses");
+    let ct = CT::with_public_key(&url, key).expect("parses");
+
+    let sth = ct.get_sth().expect("fetches");
+    assert_eq!(sth.tree_size, 10);
+    let old = crate::testlog::sth(4);
+    ct.verify_consistency(&old, &sth).expect("consistent");
+
+    let entries: Vec<Entry> = ct
+        .entries(2..9)
+        .collect::<Result<_, _>>()
+        .expect("iterates");
+    assert_eq!(
+        entries.iter().map(|e| e.index).collect::<Vec<_>>(),
+        (2..9).collect::<Vec<_>>()
+    );
+    let mut entries = ct.entries(8..12);
+    assert!(matches!(entries.next(), Some(Ok(e)) if e.index == 8));
+    assert!(matches!(entries.next(), Some(Ok(e)) if e.index == 9));
+    assert!(matches!(
+        entries.next(),
+        Some(Err(Error::HTTPStatus { .. }))
+    ));
+    assert!(entries.next().is_none());
+
+    let chain = vec![include_bytes!("../../testdata/cert.der").to_vec()];
+    assert!(matches!(
+        ct.add_chain(chain),
+        Err(Error::HTTPStatus { status, body }) if status == 400 && body == "Unknown root"
+    ));
+
+    // The builder makes blocking clients too
+    let ct = CT::builder(&url)
+        .user_agent("sect-test/1.0")
+        .timeout(std::time::Duration::from_secs(10))
+        .retry_policy(crate::retry::RetryPolicy::default())
+        .build_blocking()
+        .expect("builds");
+    assert_eq!(ct.get_sth().expect("fetches").tree_size, 10);
+    let client = reqwest::blocking::Client::new();
+    let ct = CT::builder(&url)
+        .blocking_client(client)
+        .build_blocking()
+        .expect("builds");
+    assert_eq!(ct.get_sth().expect("fetches").tree_size, 10);
+}
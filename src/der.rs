@@ -0,0 +1,239 @@
+// Minimal DER reader for the parts of X.509 that CT needs to look at.
+// Values are borrowed from the input, so encodings can be spliced back
+// together without re-encoding.
+use thiserror::Error;
+
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OID: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+
+/// Tag of a constructed, context-specific [n] field
+pub const fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+/// encode a DER value with the given tag, whose contents are the
+/// concatenation of parts
+pub fn encode(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let len: usize = parts.iter().map(|p| p.len()).sum();
+    let mut out = vec![tag];
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let skip = len_bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (len_bytes.len() - skip) as u8);
+        out.extend_from_slice(&len_bytes[skip..]);
+    }
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+    out
+}
+
+/// Errors reading DER
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("truncated DER")]
+    Truncated,
+
+    #[error("unsupported DER length encoding")]
+    BadLength,
+
+    #[error("unsupported DER tag {0:#04x}")]
+    UnsupportedTag(u8),
+
+    #[error("expected DER tag {expected:#04x}, found {actual:#04x}")]
+    UnexpectedTag { expected: u8, actual: u8 },
+
+    #[error("{0} trailing bytes after DER value")]
+    TrailingData(usize),
+
+    #[error("invalid {0}")]
+    Invalid(&'static str),
+}
+
+/// Tlv is a single DER encoded tag, length and value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tlv<'a> {
+    pub tag: u8,
+    /// The value, without the tag and length
+    pub value: &'a [u8],
+    /// The whole encoding, including the tag and length
+    pub raw: &'a [u8],
+}
+
+impl<'a> Tlv<'a> {
+    /// parse reads exactly one DER value from data
+    pub fn parse(data: &'a [u8]) -> Result<Tlv<'a>, Error> {
+        let mut reader = Reader::new(data);
+        let tlv = reader.read()?;
+        reader.finish()?;
+        Ok(tlv)
+    }
+
+    /// parse_tag reads exactly one DER value with the given tag from data
+    pub fn parse_tag(data: &'a [u8], tag: u8) -> Result<Tlv<'a>, Error> {
+        let tlv = Tlv::parse(data)?;
+        tlv.expect(tag)?;
+        Ok(tlv)
+    }
+
+    /// reader over the contents of a constructed value
+    pub fn reader(&self) -> Reader<'a> {
+        Reader::new(self.value)
+    }
+
+    fn expect(&self, tag: u8) -> Result<(), Error> {
+        if self.tag != tag {
+            return Err(Error::UnexpectedTag {
+                expected: tag,
+                actual: self.tag,
+            });
+        }
+        Ok(())
+    }
+
+    /// Contents of a BIT STRING with no unused bits
+    pub fn bit_string(&self) -> Result<&'a [u8], Error> {
+        self.expect(BIT_STRING)?;
+        match self.value.split_first() {
+            Some((0, bits)) => Ok(bits),
+            _ => Err(Error::Invalid("BIT STRING")),
+        }
+    }
+}
+
+/// Reader reads a sequence of DER values
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    /// Tag of the next value, if there is one
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// read the next value
+    pub fn read(&mut self) -> Result<Tlv<'a>, Error> {
+        let data = self.data;
+        let (&tag, rest) = data.split_first().ok_or(Error::Truncated)?;
+        if tag & 0x1f == 0x1f {
+            // High tag numbers don't appear in the structures we read
+            return Err(Error::UnsupportedTag(tag));
+        }
+        let (&first, mut rest) = rest.split_first().ok_or(Error::Truncated)?;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let len_len = (first & 0x7f) as usize;
+            // Indefinite lengths aren't DER, and nothing we read is over 4GiB
+            if len_len == 0 || len_len > 4 || rest.len() < len_len {
+                return Err(Error::BadLength);
+            }
+            let len = rest[..len_len]
+                .iter()
+                .fold(0usize, |acc, &b| acc << 8 | b as usize);
+            rest = &rest[len_len..];
+            len
+        };
+        if rest.len() < len {
+            return Err(Error::Truncated);
+        }
+        let header_len = data.len() - rest.len();
+        let tlv = Tlv {
+            tag,
+            value: &rest[..len],
+            raw: &data[..header_len + len],
+        };
+        self.data = &rest[len..];
+        Ok(tlv)
+    }
+
+    /// read the next value, which must have the given tag
+    pub fn read_tag(&mut self, tag: u8) -> Result<Tlv<'a>, Error> {
+        let tlv = self.read()?;
+        tlv.expect(tag)?;
+        Ok(tlv)
+    }
+
+    /// read the next value only if it has the given tag
+    pub fn read_optional(&mut self, tag: u8) -> Result<Option<Tlv<'a>>, Error> {
+        if self.peek_tag() != Some(tag) {
+            return Ok(None);
+        }
+        self.read().map(Some)
+    }
+
+    /// finish checks there is no data left
+    pub fn finish(&self) -> Result<(), Error> {
+        if !self.data.is_empty() {
+            return Err(Error::TrailingData(self.data.len()));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_reader() {
+    let data = &[
+        0x30, 0x0a, // SEQUENCE
+        0x02, 0x01, 0x05, // INTEGER 5
+        0x04, 0x00, // empty OCTET STRING
+        0x03, 0x03, 0x00, 0xab, 0xcd, // BIT STRING
+        0x05, 0x00, // NULL
+    ];
+    let mut outer = Reader::new(data);
+    let seq = outer.read_tag(SEQUENCE).expect("parses");
+    assert_eq!(seq.raw, &data[..12]);
+    assert_eq!(
+        outer.read_optional(NULL).expect("null").map(|n| n.raw),
+        Some(&data[12..])
+    );
+    outer.finish().expect("all read");
+
+    let mut reader = seq.reader();
+    assert_eq!(reader.read_tag(INTEGER).expect("integer").value, &[5]);
+    assert_eq!(reader.read_optional(NULL).expect("not null"), None);
+    assert!(reader
+        .read_tag(OCTET_STRING)
+        .expect("octets")
+        .value
+        .is_empty());
+    let bits = reader.read().expect("bit string");
+    assert_eq!(bits.bit_string().expect("bits"), &[0xab, 0xcd]);
+    reader.finish().expect("all read");
+
+    assert_eq!(Tlv::parse(data), Err(Error::TrailingData(2)));
+    assert_eq!(
+        Tlv::parse_tag(&data[12..], SEQUENCE),
+        Err(Error::UnexpectedTag {
+            expected: SEQUENCE,
+            actual: NULL
+        })
+    );
+    assert_eq!(Tlv::parse(&data[..11]), Err(Error::Truncated));
+    assert_eq!(Tlv::parse(&[0x30, 0x80, 0x00, 0x00]), Err(Error::BadLength));
+}
+
+#[test]
+fn test_long_length() {
+    let mut data = vec![OCTET_STRING, 0x82, 0x01, 0x00];
+    data.extend_from_slice(&[7; 256]);
+    let tlv = Tlv::parse_tag(&data, OCTET_STRING).expect("parses");
+    assert_eq!(tlv.value.len(), 256);
+    assert_eq!(tlv.raw.len(), 260);
+    assert_eq!(encode(OCTET_STRING, &[&[7; 200], &[7; 56]]), data);
+    assert_eq!(encode(SEQUENCE, &[&[5; 0x7f]]).len(), 0x81);
+    assert_eq!(encode(SEQUENCE, &[&[5; 0x80]])[..3], [SEQUENCE, 0x81, 0x80]);
+}
@@ -0,0 +1,165 @@
+// Bulk downloading of log entries.
+// A range is split into chunks aligned to the batch size, which are fetched
+// concurrently and streamed back in index order.
+use futures::{Stream, StreamExt, TryStreamExt};
+
+use crate::client::{Entry, Error, CT};
+
+/// Progress of a download, reported after each chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Entries downloaded so far
+    pub done: u64,
+    /// Entries in the whole range
+    pub total: u64,
+}
+
+/// Downloader fetches ranges of entries from a log with several
+/// get-entries requests in flight
+pub struct Downloader<'a> {
+    ct: &'a CT,
+    batch_size: u64,
+    concurrency: usize,
+    on_progress: Option<Box<dyn Fn(Progress) + Send + Sync + 'a>>,
+}
+
+impl<'a> Downloader<'a> {
+    /// Downloader fetching 256 entries per chunk, 4 chunks at a time
+    pub fn new(ct: &'a CT) -> Downloader<'a> {
+        Downloader {
+            ct,
+            batch_size: 256,
+            concurrency: 4,
+            on_progress: None,
+        }
+    }
+
+    /// Entries per chunk. This is best set to the log's get-entries limit.
+    pub fn batch_size(mut self, batch_size: u64) -> Downloader<'a> {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Maximum chunks being fetched at once
+    pub fn concurrency(mut self, concurrency: usize) -> Downloader<'a> {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Call on_progress every time a chunk has been downloaded
+    pub fn on_progress<F: Fn(Progress) + Send + Sync + 'a>(
+        mut self,
+        on_progress: F,
+    ) -> Downloader<'a> {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    /// download streams the entries with indexes in range, in order
+    pub fn download(
+        &self,
+        range: std::ops::Range<u64>,
+    ) -> impl Stream<Item = Result<Entry, Error>> + '_ {
+        let total = range.end.saturating_sub(range.start);
+        let mut done = 0;
+        futures::stream::iter(chunks(range, self.batch_size))
+            .map(move |chunk| self.ct.entries(chunk).try_collect::<Vec<_>>())
+            .buffered(self.concurrency)
+            .map_ok(move |entries| {
+                done += entries.len() as u64;
+                if let Some(on_progress) = &self.on_progress {
+                    on_progress(Progress { done, total });
+                }
+                futures::stream::iter(entries.into_iter().map(Ok))
+            })
+            .try_flatten()
+    }
+}
+
+/// chunks splits range at multiples of batch_size, as they're needed
+fn chunks(
+    range: std::ops::Range<u64>,
+    batch_size: u64,
+) -> impl Iterator<Item = std::ops::Range<u64>> {
+    let mut start = range.start;
+    std::iter::from_fn(move || {
+        if start >= range.end {
+            return None;
+        }
+        let end = (start / batch_size + 1)
+            .saturating_mul(batch_size)
+            .min(range.end);
+        let chunk = start..end;
+        start = end;
+        Some(chunk)
+    })
+}
+
+#[test]
+fn test_chunks() {
+    let collect = |range, batch_size| chunks(range, batch_size).collect::<Vec<_>>();
+    assert_eq!(collect(0..10, 4), vec![0..4, 4..8, 8..10]);
+    assert_eq!(collect(3..13, 4), vec![3..4, 4..8, 8..12, 12..13]);
+    assert_eq!(collect(4..8, 4), vec![4..8]);
+    assert!(collect(5..5, 4).is_empty());
+    assert_eq!(
+        collect(u64::MAX - 1..u64::MAX, 256),
+        vec![u64::MAX - 1..u64::MAX]
+    );
+    // A whole log's worth of chunks isn't built up front
+    assert_eq!(chunks(0..u64::MAX, 256).nth(2), Some(512..768));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_download() {
+    use std::sync::{Arc, Mutex};
+
+    let url = crate::testlog::serve(|request| crate::testlog::get_entries(request, 100, 3)).await;
+    let ct = CT::new(&url).expect("parses");
+
+    let progress = Arc::new(Mutex::new(vec![]));
+    let seen = progress.clone();
+    let downloader = Downloader::new(&ct)
+        .batch_size(8)
+        .concurrency(3)
+        .on_progress(move |p| seen.lock().unwrap().push(p));
+    let entries: Vec<Entry> = downloader
+        .download(5..50)
+        .try_collect()
+        .await
+        .expect("downloads");
+    assert_eq!(
+        entries.iter().map(|e| e.index).collect::<Vec<_>>(),
+        (5..50).collect::<Vec<_>>()
+    );
+    for entry in &entries {
+        assert_eq!(entry.leaf.timestamp(), entry.index);
+    }
+
+    let progress = progress.lock().unwrap().clone();
+    // 5..8, then 8 entry chunks, then 48..50
+    assert_eq!(progress.len(), 7);
+    assert_eq!(progress[0], Progress { done: 3, total: 45 });
+    assert_eq!(
+        progress[1],
+        Progress {
+            done: 11,
+            total: 45
+        }
+    );
+    assert_eq!(
+        *progress.last().unwrap(),
+        Progress {
+            done: 45,
+            total: 45
+        }
+    );
+
+    let err = downloader
+        .download(90..110)
+        .try_collect::<Vec<_>>()
+        .await
+        .expect_err("fails");
+    assert!(matches!(err, Error::HTTPStatus { .. }));
+}
@@ -0,0 +1,121 @@
+// SCTs embedded in a final certificate, S3.3.
+// The SCTs sign the precertificate, which is rebuilt from the final
+// certificate by removing the SCT list extension.
+use thiserror::Error;
+
+use crate::entry::{LogEntry, PreCert};
+use crate::sct::{SignedCertificateTimestamp, SignedCertificateTimestampList};
+use crate::signature::PublicKey;
+
+/// Errors extracting embedded SCTs
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("malformed certificate: {0}")]
+    Certificate(#[from] crate::der::Error),
+
+    #[error("certificate has no embedded SCT list")]
+    NoSCTList,
+
+    #[error("malformed SCT list: {0}")]
+    SCT(#[from] crate::sct::Error),
+}
+
+/// EmbeddedSCTs are the SCTs of a final certificate, with the precert_entry
+/// they were issued for
+#[derive(Debug, Clone)]
+pub struct EmbeddedSCTs {
+    scts: SignedCertificateTimestampList,
+    precert: PreCert,
+}
+
+impl EmbeddedSCTs {
+    /// Extract the SCT list from certificate, and rebuild the PreCert its
+    /// SCTs sign. issuer is the DER certificate that issued certificate.
+    pub fn from_certificate(certificate: &[u8], issuer: &[u8]) -> Result<EmbeddedSCTs, Error> {
+        let mut tbs = crate::x509::TbsCertificate::from_certificate(certificate)?;
+        let extension = tbs
+            .remove_extension(crate::x509::OID_CT_SCT_LIST)
+            .ok_or(Error::NoSCTList)?;
+        let scts = SignedCertificateTimestampList::from_extension_value(extension.value)?;
+
+        let issuer_key_hash = crate::x509::issuer_key_hash(issuer)?;
+        Ok(EmbeddedSCTs {
+            scts,
+            precert: PreCert::new(issuer_key_hash, tbs.encode()),
+        })
+    }
+
+    pub fn scts(&self) -> &[SignedCertificateTimestamp] {
+        self.scts.scts()
+    }
+
+    /// The PreCert the SCTs were issued for
+    pub fn precert(&self) -> &PreCert {
+        &self.precert
+    }
+
+    /// The precert_entry the SCTs were issued for
+    pub fn log_entry(&self) -> LogEntry {
+        LogEntry::PrecertEntry(self.precert.clone())
+    }
+
+    /// verify checks sct, one of these SCTs, was issued by the log with
+    /// public_key for this certificate
+    pub fn verify(
+        &self,
+        sct: &SignedCertificateTimestamp,
+        public_key: &PublicKey,
+    ) -> Result<(), crate::sct::Error> {
+        sct.verify(public_key, &self.log_entry())
+    }
+}
+
+#[test]
+fn test_embedded_scts() {
+    use crate::signature::{TEST_EC_PUB, TEST_RSA_PUB};
+
+    let cert = include_bytes!("../testdata/cert-scts.der");
+    let ca = include_bytes!("../testdata/ca.der");
+    let embedded = EmbeddedSCTs::from_certificate(cert, ca).expect("extracts");
+
+    // The rebuilt PreCert matches the one logged for the precertificate
+    let precert_chain = vec![
+        include_bytes!("../testdata/precert.der").to_vec(),
+        ca.to_vec(),
+    ];
+    assert_eq!(
+        embedded.log_entry(),
+        LogEntry::from_pre_chain(&precert_chain).expect("builds")
+    );
+
+    let ec_key = PublicKey::from_pem(TEST_EC_PUB.as_bytes()).expect("parses");
+    let rsa_key = PublicKey::from_pem(TEST_RSA_PUB.as_bytes()).expect("parses");
+    let scts = embedded.scts();
+    assert_eq!(scts.len(), 2);
+    assert_eq!(*scts[0].log_id(), ec_key.log_id());
+    assert_eq!(scts[0].timestamp(), 1767225600000);
+    embedded.verify(&scts[0], &ec_key).expect("verifies");
+    embedded.verify(&scts[1], &rsa_key).expect("verifies");
+    assert!(matches!(
+        embedded.verify(&scts[0], &rsa_key),
+        Err(crate::sct::Error::WrongLogID)
+    ));
+
+    // The issuer key is part of what's signed
+    let wrong_issuer = EmbeddedSCTs::from_certificate(cert, cert).expect("extracts");
+    assert!(matches!(
+        wrong_issuer.verify(&scts[0], &ec_key),
+        Err(crate::sct::Error::Signature(
+            crate::signature::Error::BadSignature
+        ))
+    ));
+
+    assert!(matches!(
+        EmbeddedSCTs::from_certificate(include_bytes!("../testdata/cert.der"), ca),
+        Err(Error::NoSCTList)
+    ));
+    assert!(matches!(
+        EmbeddedSCTs::from_certificate(&cert[..100], ca),
+        Err(Error::Certificate(_))
+    ));
+}
@@ -1,12 +1,104 @@
+use thiserror::Error;
+use tls_codec::{Deserialize, Serialize};
 use tls_codec_derive::{TlsDeserialize, TlsSerialize, TlsSize};
 
+/// Errors decoding log entries
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("TLS decoding failed: {0}")]
+    TLS(#[from] tls_codec::Error),
+
+    #[error("unknown MerkleTreeLeaf version {0}")]
+    UnknownVersion(u8),
+
+    #[error("{0} trailing bytes after entry")]
+    TrailingData(usize),
+
+    #[error("empty certificate chain")]
+    EmptyChain,
+
+    #[error("bad precertificate chain: {0}")]
+    Precert(#[from] crate::precert::Error),
+
+    #[error("malformed certificate: {0}")]
+    Certificate(#[from] crate::der::Error),
+}
+
+/// decode_leaf_input decodes the leaf_input of a get-entries or
+/// get-entry-and-proof response.
+/// The whole input must be a single v1 MerkleTreeLeaf.
+pub fn decode_leaf_input(leaf_input: &[u8]) -> Result<MerkleTreeLeaf, Error> {
+    // Check the version first, as later versions may be laid out differently
+    if let Some(&version) = leaf_input.first() {
+        if version != MerkleTreeLeaf::V1 {
+            return Err(Error::UnknownVersion(version));
+        }
+    }
+    let mut buf = leaf_input;
+    let leaf = MerkleTreeLeaf::tls_deserialize(&mut buf)?;
+    if !buf.is_empty() {
+        return Err(Error::TrailingData(buf.len()));
+    }
+    Ok(leaf)
+}
+
 #[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
-// MerkleTreeLeaf is described in S3.4.
-struct MerkleTreeLeaf {
+/// MerkleTreeLeaf is described in S3.4.
+pub struct MerkleTreeLeaf {
     version: u8,
     signed_entry: MerkleLeaf,
 }
 
+impl MerkleTreeLeaf {
+    const V1: u8 = 0;
+
+    /// New v1 MerkleTreeLeaf for a log entry with the given timestamp
+    pub fn new(timestamp: u64, log_entry: LogEntry, extensions: Vec<u8>) -> MerkleTreeLeaf {
+        MerkleTreeLeaf {
+            version: MerkleTreeLeaf::V1,
+            signed_entry: MerkleLeaf::TimeStampedEntry(TimeStampedEntry {
+                timestamp,
+                log_entry,
+                extensions: extensions.into(),
+            }),
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn timestamped_entry(&self) -> &TimeStampedEntry {
+        match &self.signed_entry {
+            MerkleLeaf::TimeStampedEntry(entry) => entry,
+        }
+    }
+
+    /// Milliseconds since epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamped_entry().timestamp()
+    }
+
+    pub fn entry_type(&self) -> LogEntryType {
+        self.log_entry().entry_type()
+    }
+
+    pub fn log_entry(&self) -> &LogEntry {
+        self.timestamped_entry().log_entry()
+    }
+
+    pub fn extensions(&self) -> &[u8] {
+        self.timestamped_entry().extensions()
+    }
+
+    /// TLS encoding of this leaf, as used for leaf_input and the Merkle leaf hash
+    pub fn to_bytes(&self) -> Result<Vec<u8>, tls_codec::Error> {
+        let mut buf = Vec::new();
+        self.tls_serialize(&mut buf)?;
+        Ok(buf)
+    }
+}
+
 #[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
 #[repr(u8)]
 // MerkleLeaf is the enum MerkleLeafType and data that always follows
@@ -17,40 +109,205 @@ enum MerkleLeaf {
 }
 
 #[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
-// TimeStampedEntry is described in S3.4.
-struct TimeStampedEntry {
+/// TimeStampedEntry is described in S3.4.
+pub struct TimeStampedEntry {
     timestamp: u64, // Milliseconds since epoch
     log_entry: LogEntry,
     extensions: tls_codec::TlsByteVecU16,
 }
 
-// LogEntry is the LogEntryType and signed_entry
-// It occurs in both the SignedCertificateTimestamp and TimeStampedEntry
+impl TimeStampedEntry {
+    /// Milliseconds since epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn log_entry(&self) -> &LogEntry {
+        &self.log_entry
+    }
+
+    pub fn extensions(&self) -> &[u8] {
+        self.extensions.as_slice()
+    }
+}
+
+/// LogEntryType is described in S3.1.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum LogEntryType {
+    X509Entry,
+    PrecertEntry,
+}
+
+/// LogEntry is the LogEntryType and signed_entry
+/// It occurs in both the SignedCertificateTimestamp and TimeStampedEntry
 #[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
 #[repr(u16)]
-enum LogEntry {
+pub enum LogEntry {
     #[tls_codec(discriminant = 0)]
     X509Entry(Asn1Cert),
     #[tls_codec(discriminant = 1)]
     PrecertEntry(PreCert),
 }
 
+impl LogEntry {
+    /// x509_entry for the leaf of a chain submitted to add-chain
+    pub fn from_chain(chain: &[Vec<u8>]) -> Result<LogEntry, Error> {
+        let leaf = chain.first().ok_or(Error::EmptyChain)?;
+        crate::x509::tbs_certificate(leaf)?;
+        Ok(LogEntry::X509Entry(Asn1Cert::new(leaf.clone())))
+    }
+
+    /// precert_entry for the precertificate of a chain submitted to add-pre-chain,
+    /// prepared as described in crate::precert::prepare
+    pub fn from_pre_chain(chain: &[Vec<u8>]) -> Result<LogEntry, Error> {
+        Ok(LogEntry::PrecertEntry(crate::precert::prepare(chain)?))
+    }
+
+    pub fn entry_type(&self) -> LogEntryType {
+        match self {
+            LogEntry::X509Entry(_) => LogEntryType::X509Entry,
+            LogEntry::PrecertEntry(_) => LogEntryType::PrecertEntry,
+        }
+    }
+
+    /// DER certificate of an x509_entry, or None for a precert_entry
+    pub fn certificate(&self) -> Option<&[u8]> {
+        match self {
+            LogEntry::X509Entry(cert) => Some(cert.der()),
+            LogEntry::PrecertEntry(_) => None,
+        }
+    }
+
+    /// PreCert of a precert_entry, or None for an x509_entry
+    pub fn precert(&self) -> Option<&PreCert> {
+        match self {
+            LogEntry::X509Entry(_) => None,
+            LogEntry::PrecertEntry(precert) => Some(precert),
+        }
+    }
+}
+
 #[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
-struct Asn1Cert {
+pub struct Asn1Cert {
     opaque: crate::tlsvec24::TlsVec24<u8>,
 }
 
-// PreCert is defined in S3.2.
+impl Asn1Cert {
+    pub fn new(der: Vec<u8>) -> Asn1Cert {
+        Asn1Cert { opaque: der.into() }
+    }
+
+    /// DER encoded certificate
+    pub fn der(&self) -> &[u8] {
+        self.opaque.as_slice()
+    }
+}
+
+/// PreCert is defined in S3.2.
 #[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
-struct PreCert {
+pub struct PreCert {
     issuer_key_hash: [u8; 32],
     tbs_certificate: crate::tlsvec24::TlsVec24<u8>,
 }
 
+impl PreCert {
+    pub fn new(issuer_key_hash: [u8; 32], tbs_certificate: Vec<u8>) -> PreCert {
+        PreCert {
+            issuer_key_hash,
+            tbs_certificate: tbs_certificate.into(),
+        }
+    }
+
+    /// SHA-256 hash of the issuer's SubjectPublicKeyInfo
+    pub fn issuer_key_hash(&self) -> &[u8; 32] {
+        &self.issuer_key_hash
+    }
+
+    /// DER encoded TBSCertificate, with the poison extension removed
+    pub fn tbs_certificate(&self) -> &[u8] {
+        self.tbs_certificate.as_slice()
+    }
+}
+
+/// X509ChainEntry is the extra_data of an x509_entry in get-entries, S4.6.
+/// Unlike the X509ChainEntry of S3.1, it doesn't repeat the leaf_certificate,
+/// as that is already in the leaf_input.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
+pub struct X509ChainEntry {
+    certificate_chain: crate::tlsvec24::TlsVec24<Asn1Cert>,
+}
+
+impl X509ChainEntry {
+    pub fn new(certificate_chain: Vec<Asn1Cert>) -> X509ChainEntry {
+        X509ChainEntry {
+            certificate_chain: certificate_chain.into(),
+        }
+    }
+
+    /// Certificates chaining the leaf up to a root accepted by the log
+    pub fn certificate_chain(&self) -> &[Asn1Cert] {
+        self.certificate_chain.as_slice()
+    }
+}
+
+/// PrecertChainEntry is the extra_data of a precert_entry in get-entries.
+/// It is described in S3.1.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
+pub struct PrecertChainEntry {
+    pre_certificate: Asn1Cert,
+    precertificate_chain: crate::tlsvec24::TlsVec24<Asn1Cert>,
+}
+
+impl PrecertChainEntry {
+    pub fn new(
+        pre_certificate: Asn1Cert,
+        precertificate_chain: Vec<Asn1Cert>,
+    ) -> PrecertChainEntry {
+        PrecertChainEntry {
+            pre_certificate,
+            precertificate_chain: precertificate_chain.into(),
+        }
+    }
+
+    /// The submitted precertificate, including the poison extension
+    pub fn pre_certificate(&self) -> &Asn1Cert {
+        &self.pre_certificate
+    }
+
+    /// Certificates chaining the precertificate up to a root accepted by the log
+    pub fn precertificate_chain(&self) -> &[Asn1Cert] {
+        self.precertificate_chain.as_slice()
+    }
+}
+
+/// full_chain decodes the extra_data belonging to log_entry, and returns the
+/// complete chain of DER certificates starting with the (pre)certificate.
+pub fn full_chain(log_entry: &LogEntry, extra_data: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut buf = extra_data;
+    let chain: Vec<Vec<u8>> = match log_entry {
+        LogEntry::X509Entry(cert) => {
+            let extra = X509ChainEntry::tls_deserialize(&mut buf)?;
+            std::iter::once(cert)
+                .chain(extra.certificate_chain())
+                .map(|c| c.der().to_vec())
+                .collect()
+        }
+        LogEntry::PrecertEntry(_) => {
+            let extra = PrecertChainEntry::tls_deserialize(&mut buf)?;
+            std::iter::once(extra.pre_certificate())
+                .chain(extra.precertificate_chain())
+                .map(|c| c.der().to_vec())
+                .collect()
+        }
+    };
+    if !buf.is_empty() {
+        return Err(Error::TrailingData(buf.len()));
+    }
+    Ok(chain)
+}
+
 #[test]
 fn test_x509_entry_deserialize() {
-    use tls_codec::{Deserialize, Serialize};
-
     // https://oak.ct.letsencrypt.org/2022/ct/v1/get-entries?start=5000&end=5000
     let x509 = &[
         0x00u8, // Version 0
@@ -106,7 +363,7 @@ fn test_x509_entry_deserialize() {
     // use known offsets to get a copy of the cert out of the sample data
     let extracted_cert = &x509[15..15 + 608];
 
-    let mut buf = x509.clone();
+    let mut buf = x509;
     let deserialized = MerkleTreeLeaf::tls_deserialize(&mut buf).expect("should deserialize");
 
     assert_eq!(0, buf.len(), "should read all data");
@@ -134,8 +391,6 @@ fn test_x509_entry_deserialize() {
 
 #[test]
 fn test_precert() {
-    use tls_codec::{Deserialize, Serialize};
-
     // https://oak.ct.letsencrypt.org/2023/ct/v1/get-entries?start=319918522&end=319918522
     let data = &[
         0x00u8, // Version 0
@@ -213,7 +468,7 @@ fn test_precert() {
         0x28, 0x6d,
     ];
 
-    let mut buf = data.clone();
+    let mut buf = data;
     let deserialized = MerkleTreeLeaf::tls_deserialize(&mut buf).expect("should deserialize");
     assert_eq!(0, buf.len(), "should read all data");
 
@@ -239,3 +494,158 @@ fn test_precert() {
     assert_eq!(data.len(), reserialized);
     assert_eq!(data, buf.as_slice());
 }
+
+#[test]
+fn test_decode_leaf_input() {
+    let leaf = &[
+        0x00u8, // Version 0
+        0x00,   // Timestamped Entry
+        0x00, 0x00, 0x01, 0x69, 0x11, 0xb1, 0x70, 0xb2, // u64 millis timestamp
+        0x00, 0x00, // x509 entry
+        0x00, 0x00, 0x03, // 24 bit length
+        0x30, 0x01, 0x00, // "certificate"
+        0x00, 0x02, 0xab, 0xcd, // 2 bytes of extensions
+    ] as &[u8];
+
+    let decoded = decode_leaf_input(leaf).expect("should decode");
+    assert_eq!(decoded.version(), 0);
+    assert_eq!(decoded.timestamp(), 1550780035250);
+    assert_eq!(decoded.entry_type(), LogEntryType::X509Entry);
+    assert_eq!(
+        decoded.log_entry().certificate(),
+        Some(&[0x30u8, 0x01, 0x00][..])
+    );
+    assert_eq!(decoded.log_entry().precert(), None);
+    assert_eq!(decoded.extensions(), &[0xab, 0xcd]);
+    assert_eq!(decoded.to_bytes().expect("serializes"), leaf);
+    assert_eq!(
+        decoded,
+        MerkleTreeLeaf::new(
+            1550780035250,
+            LogEntry::X509Entry(Asn1Cert::new(vec![0x30, 0x01, 0x00])),
+            vec![0xab, 0xcd]
+        )
+    );
+
+    let mut trailing = leaf.to_vec();
+    trailing.push(0);
+    assert!(matches!(
+        decode_leaf_input(&trailing),
+        Err(Error::TrailingData(1))
+    ));
+
+    let mut v2 = leaf.to_vec();
+    v2[0] = 1;
+    assert!(matches!(
+        decode_leaf_input(&v2),
+        Err(Error::UnknownVersion(1))
+    ));
+    // A later version needn't be laid out like v1
+    assert!(matches!(
+        decode_leaf_input(&[0x02, 0xff, 0xff, 0xff]),
+        Err(Error::UnknownVersion(2))
+    ));
+
+    let mut unknown_type = leaf.to_vec();
+    unknown_type[11] = 2;
+    assert!(matches!(
+        decode_leaf_input(&unknown_type),
+        Err(Error::TLS(_))
+    ));
+
+    assert!(matches!(decode_leaf_input(&leaf[..10]), Err(Error::TLS(_))));
+}
+
+#[test]
+fn test_full_chain() {
+    let x509_extra = &[
+        0x00u8, 0x00, 0x0b, // 24 bit length of the chain
+        0x00, 0x00, 0x02, 0x30, 0x00, // intermediate
+        0x00, 0x00, 0x03, 0x30, 0x01, 0x01, // root
+    ] as &[u8];
+    let leaf = LogEntry::X509Entry(Asn1Cert::new(vec![0x30, 0x01, 0x02]));
+
+    let chain = full_chain(&leaf, x509_extra).expect("should decode");
+    assert_eq!(
+        chain,
+        vec![
+            vec![0x30, 0x01, 0x02],
+            vec![0x30, 0x00],
+            vec![0x30, 0x01, 0x01]
+        ]
+    );
+    assert_eq!(
+        X509ChainEntry::tls_deserialize(&mut &x509_extra[..]).expect("should decode"),
+        X509ChainEntry::new(vec![
+            Asn1Cert::new(vec![0x30, 0x00]),
+            Asn1Cert::new(vec![0x30, 0x01, 0x01])
+        ])
+    );
+
+    let mut trailing = x509_extra.to_vec();
+    trailing.push(0);
+    assert!(matches!(
+        full_chain(&leaf, &trailing),
+        Err(Error::TrailingData(1))
+    ));
+    assert!(matches!(
+        full_chain(&leaf, &x509_extra[..8]),
+        Err(Error::TLS(_))
+    ));
+
+    let precert_extra = &[
+        0x00u8, 0x00, 0x02, 0x30, 0x00, // pre_certificate
+        0x00, 0x00, 0x05, // 24 bit length of the chain
+        0x00, 0x00, 0x02, 0x30, 0x00, // issuer
+    ] as &[u8];
+    let precert = LogEntry::PrecertEntry(PreCert::new([0; 32], vec![0x30, 0x00]));
+
+    let chain = full_chain(&precert, precert_extra).expect("should decode");
+    assert_eq!(chain, vec![vec![0x30, 0x00], vec![0x30, 0x00]]);
+
+    let entry = PrecertChainEntry::new(
+        Asn1Cert::new(vec![0x30, 0x00]),
+        vec![Asn1Cert::new(vec![0x30, 0x00])],
+    );
+    let mut buf = Vec::new();
+    entry.tls_serialize(&mut buf).expect("should serialize");
+    assert_eq!(buf, precert_extra);
+}
+
+#[test]
+fn test_log_entry_from_chain() {
+    let ca = include_bytes!("../testdata/ca.der").to_vec();
+    let cert = include_bytes!("../testdata/cert.der").to_vec();
+    let precert = include_bytes!("../testdata/precert.der").to_vec();
+
+    let entry = LogEntry::from_chain(&[cert.clone(), ca.clone()]).expect("builds");
+    assert_eq!(entry.certificate(), Some(cert.as_slice()));
+    assert!(matches!(LogEntry::from_chain(&[]), Err(Error::EmptyChain)));
+    assert!(matches!(
+        LogEntry::from_chain(&[vec![0x30, 0x00]]),
+        Err(Error::Certificate(_))
+    ));
+
+    let entry = LogEntry::from_pre_chain(&[precert.clone(), ca.clone()]).expect("builds");
+    let precert_entry = entry.precert().expect("is a precert");
+    let ca_spki = crate::x509::TbsCertificate::from_certificate(&ca)
+        .expect("parses")
+        .subject_public_key_info()
+        .to_vec();
+    assert_eq!(
+        precert_entry.issuer_key_hash().as_slice(),
+        ring::digest::digest(&ring::digest::SHA256, &ca_spki).as_ref()
+    );
+    let mut tbs =
+        crate::x509::TbsCertificate::parse(precert_entry.tbs_certificate()).expect("parses");
+    assert!(tbs.remove_extension(crate::x509::OID_CT_POISON).is_none());
+
+    assert!(matches!(
+        LogEntry::from_pre_chain(&[precert]),
+        Err(Error::Precert(crate::precert::Error::MissingIssuer))
+    ));
+    assert!(matches!(
+        LogEntry::from_pre_chain(&[cert, ca]),
+        Err(Error::Precert(crate::precert::Error::NoPoison))
+    ));
+}
@@ -1,4 +1,29 @@
 pub mod api;
+pub mod checkpoint;
+#[cfg(feature = "client")]
 pub mod client;
+mod der;
+#[cfg(feature = "client")]
+pub mod download;
+pub mod embedded;
 pub mod entry;
+pub mod loglist;
+pub mod merkle;
+#[cfg(feature = "client")]
+pub mod monitor;
+pub mod ocsp;
+pub mod precert;
+#[cfg(feature = "client")]
+pub mod ratelimit;
+pub mod registry;
+#[cfg(feature = "client")]
+pub mod retry;
+pub mod sct;
+pub mod signature;
+#[cfg(all(test, feature = "client"))]
+mod testlog;
 mod tlsvec24;
+mod x509;
+
+/// Errors reading the DER in certificates and OCSP responses
+pub use der::Error as DERError;
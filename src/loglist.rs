@@ -0,0 +1,279 @@
+// The log list published for Chrome and Apple, in the v3 schema:
+// https://www.gstatic.com/ct/log_list/v3/log_list_schema.json
+// Timestamps are kept as the RFC 3339 strings the list uses.
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api::{b64decode, b64encode};
+use crate::signature::PublicKey;
+
+/// Errors loading a log list, or using a log from it
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("can't read log list: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("malformed log list: {0}")]
+    JSON(#[from] serde_json::Error),
+
+    #[error("bad log key: {0}")]
+    Key(#[from] crate::signature::Error),
+
+    #[error("log ID doesn't match the log's key")]
+    LogIDMismatch,
+
+    #[error("bad log URL: {0}")]
+    URL(#[from] url::ParseError),
+}
+
+/// LogList is the top level of log_list.json
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogList {
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub log_list_timestamp: Option<String>,
+    pub operators: Vec<Operator>,
+}
+
+/// Operator is an organisation running logs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Operator {
+    pub name: String,
+    #[serde(default)]
+    pub email: Vec<String>,
+    #[serde(default)]
+    pub logs: Vec<Log>,
+}
+
+/// Log is a single RFC 6962 log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Log {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub log_id: Vec<u8>,
+    /// DER SubjectPublicKeyInfo
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub key: Vec<u8>,
+    pub url: String,
+    /// Maximum merge delay, in seconds
+    pub mmd: u64,
+    #[serde(default)]
+    pub state: Option<State>,
+    #[serde(default)]
+    pub temporal_interval: Option<TemporalInterval>,
+    #[serde(default)]
+    pub log_type: Option<String>,
+}
+
+/// State of a log, and when it entered that state
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    Pending(StateTimestamp),
+    Qualified(StateTimestamp),
+    Usable(StateTimestamp),
+    Readonly(ReadonlyState),
+    Retired(StateTimestamp),
+    Rejected(StateTimestamp),
+}
+
+/// StateKind is a State without its details, for filtering logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StateKind {
+    Pending,
+    Qualified,
+    Usable,
+    Readonly,
+    Retired,
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StateTimestamp {
+    pub timestamp: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReadonlyState {
+    pub timestamp: String,
+    pub final_tree_head: FinalTreeHead,
+}
+
+/// FinalTreeHead is the last STH of a read-only log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FinalTreeHead {
+    #[serde(serialize_with = "b64encode", deserialize_with = "b64decode")]
+    pub sha256_root_hash: Vec<u8>,
+    pub tree_size: u64,
+}
+
+/// TemporalInterval is the range of certificate expiry times a sharded
+/// log accepts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemporalInterval {
+    pub start_inclusive: String,
+    pub end_exclusive: String,
+}
+
+impl State {
+    pub fn kind(&self) -> StateKind {
+        match self {
+            State::Pending(_) => StateKind::Pending,
+            State::Qualified(_) => StateKind::Qualified,
+            State::Usable(_) => StateKind::Usable,
+            State::Readonly(_) => StateKind::Readonly,
+            State::Retired(_) => StateKind::Retired,
+            State::Rejected(_) => StateKind::Rejected,
+        }
+    }
+
+    /// When the log entered this state
+    pub fn timestamp(&self) -> &str {
+        match self {
+            State::Pending(s)
+            | State::Qualified(s)
+            | State::Usable(s)
+            | State::Retired(s)
+            | State::Rejected(s) => &s.timestamp,
+            State::Readonly(s) => &s.timestamp,
+        }
+    }
+}
+
+impl LogList {
+    /// Parse log_list.json
+    pub fn from_json(json: &[u8]) -> Result<LogList, Error> {
+        Ok(serde_json::from_slice(json)?)
+    }
+
+    /// Read and parse a log_list.json file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<LogList, Error> {
+        LogList::from_json(&std::fs::read(path)?)
+    }
+
+    /// All logs, of every operator
+    pub fn logs(&self) -> impl Iterator<Item = &Log> {
+        self.operators.iter().flat_map(|o| o.logs.iter())
+    }
+
+    /// The operator with the given name
+    pub fn operator(&self, name: &str) -> Option<&Operator> {
+        self.operators.iter().find(|o| o.name == name)
+    }
+
+    /// Logs currently in the given state
+    pub fn logs_in_state(&self, state: StateKind) -> impl Iterator<Item = &Log> {
+        self.logs().filter(move |l| l.state_kind() == Some(state))
+    }
+
+    /// The log with the given log ID
+    pub fn log(&self, log_id: &[u8]) -> Option<&Log> {
+        self.logs().find(|l| l.log_id == log_id)
+    }
+
+    /// The public keys of every log, keyed by log ID, for verifying SCTs
+    pub fn public_keys(&self) -> Result<HashMap<[u8; 32], PublicKey>, Error> {
+        self.logs()
+            .map(|l| {
+                let key = l.public_key()?;
+                Ok((key.log_id(), key))
+            })
+            .collect()
+    }
+}
+
+impl Log {
+    pub fn state_kind(&self) -> Option<StateKind> {
+        self.state.as_ref().map(State::kind)
+    }
+
+    /// The log's public key, which is checked against its log ID
+    pub fn public_key(&self) -> Result<PublicKey, Error> {
+        let key = PublicKey::from_der(&self.key)?;
+        if key.log_id() != self.log_id.as_slice() {
+            return Err(Error::LogIDMismatch);
+        }
+        Ok(key)
+    }
+
+    /// A CT client for this log, which verifies responses against its key
+    #[cfg(feature = "client")]
+    pub fn client(&self) -> Result<crate::client::CT, Error> {
+        Ok(crate::client::CT::with_public_key(
+            &self.url,
+            self.public_key()?,
+        )?)
+    }
+}
+
+#[test]
+fn test_log_list() {
+    let list = LogList::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/testdata/log_list.json"
+    ))
+    .expect("parses");
+    assert_eq!(list.logs().count(), 3);
+
+    let operator = list.operator("Sect Test Operator").expect("exists");
+    assert_eq!(operator.email, vec!["ct@example.com"]);
+    assert_eq!(operator.logs.len(), 2);
+    assert!(list.operator("Nobody").is_none());
+
+    let usable: Vec<_> = list.logs_in_state(StateKind::Usable).collect();
+    assert_eq!(usable.len(), 1);
+    let log = usable[0];
+    assert_eq!(log.mmd, 86400);
+    assert_eq!(
+        log.temporal_interval,
+        Some(TemporalInterval {
+            start_inclusive: "2026-01-01T00:00:00Z".to_string(),
+            end_exclusive: "2027-01-01T00:00:00Z".to_string(),
+        })
+    );
+    let key = log.public_key().expect("parses");
+    assert_eq!(
+        key.der(),
+        PublicKey::from_pem(crate::signature::TEST_EC_PUB.as_bytes())
+            .expect("parses")
+            .der()
+    );
+    #[cfg(feature = "client")]
+    log.client().expect("builds");
+    assert_eq!(list.log(&key.log_id()).map(|l| &l.url), Some(&log.url));
+
+    let readonly: Vec<_> = list.logs_in_state(StateKind::Readonly).collect();
+    match &readonly[0].state {
+        Some(State::Readonly(state)) => {
+            assert_eq!(state.final_tree_head.tree_size, 1234);
+            assert_eq!(state.timestamp, "2026-03-01T00:00:00Z");
+        }
+        state => panic!("unexpected state {state:?}"),
+    }
+    assert_eq!(readonly[0].log_type.as_deref(), Some("test"));
+    assert_eq!(list.logs_in_state(StateKind::Retired).count(), 1);
+    assert_eq!(list.logs_in_state(StateKind::Rejected).count(), 0);
+
+    let keys = list.public_keys().expect("parses");
+    assert_eq!(keys.len(), 3);
+    assert!(keys.contains_key(&key.log_id()));
+
+    let mut mismatch = log.clone();
+    mismatch.log_id = vec![0; 32];
+    assert!(matches!(mismatch.public_key(), Err(Error::LogIDMismatch)));
+}
+
+#[test]
+fn test_state() {
+    let state: State =
+        serde_json::from_str(r#"{"qualified": {"timestamp": "2026-01-01T00:00:00Z"}}"#)
+            .expect("parses");
+    assert_eq!(state.kind(), StateKind::Qualified);
+    assert_eq!(state.timestamp(), "2026-01-01T00:00:00Z");
+    assert!(serde_json::from_str::<State>(r#"{"frozen": {"timestamp": ""}}"#).is_err());
+}
@@ -0,0 +1,512 @@
+use ring::digest::{Context, SHA256};
+use thiserror::Error;
+
+/// A SHA-256 Merkle tree hash
+pub type Hash = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// leaf_hash is the Merkle leaf hash of a MerkleTreeLeaf, described in S3.4.
+pub fn leaf_hash(leaf: &crate::entry::MerkleTreeLeaf) -> Result<Hash, tls_codec::Error> {
+    Ok(leaf_data_hash(&leaf.to_bytes()?))
+}
+
+/// leaf_data_hash is SHA-256(0x00 || data), the hash of a leaf in S2.1.
+/// For log entries, data is the leaf_input from get-entries.
+pub fn leaf_data_hash(data: &[u8]) -> Hash {
+    let mut ctx = Context::new(&SHA256);
+    ctx.update(&[LEAF_PREFIX]);
+    ctx.update(data);
+    finish(ctx)
+}
+
+/// node_hash is SHA-256(0x01 || left || right), the hash of an interior node in S2.1.
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut ctx = Context::new(&SHA256);
+    ctx.update(&[NODE_PREFIX]);
+    ctx.update(left);
+    ctx.update(right);
+    finish(ctx)
+}
+
+/// tree_hash is the Merkle Tree Hash (MTH) of a list of leaf data, described in S2.1.
+pub fn tree_hash<T: AsRef<[u8]>>(leaves: &[T]) -> Hash {
+    let hashes: Vec<Hash> = leaves.iter().map(|l| leaf_data_hash(l.as_ref())).collect();
+    tree_hash_from_leaf_hashes(&hashes)
+}
+
+/// tree_hash_from_leaf_hashes is the Merkle Tree Hash of a tree whose leaves
+/// have already been hashed with leaf_data_hash.
+pub fn tree_hash_from_leaf_hashes(hashes: &[Hash]) -> Hash {
+    match hashes.len() {
+        0 => finish(Context::new(&SHA256)),
+        1 => hashes[0],
+        n => {
+            let k = split_point(n as u64) as usize;
+            node_hash(
+                &tree_hash_from_leaf_hashes(&hashes[..k]),
+                &tree_hash_from_leaf_hashes(&hashes[k..]),
+            )
+        }
+    }
+}
+
+/// split_point is the largest power of two smaller than n, for n > 1.
+fn split_point(n: u64) -> u64 {
+    debug_assert!(n > 1);
+    1 << (63 - (n - 1).leading_zeros())
+}
+
+/// Errors verifying Merkle proofs
+#[derive(Error, Debug, PartialEq)]
+pub enum ProofError {
+    #[error("leaf index {leaf_index} is not in a tree of size {tree_size}")]
+    LeafIndexOutOfRange { leaf_index: u64, tree_size: u64 },
+
+    #[error("tree of size {first} can't be extended by smaller tree of size {second}")]
+    TreeShrunk { first: u64, second: u64 },
+
+    #[error("proof has {actual} hashes, expected {expected}")]
+    WrongProofSize { expected: usize, actual: usize },
+
+    #[error("hash is {0} bytes, expected 32")]
+    BadHashLength(usize),
+
+    #[error("calculated root hash doesn't match the expected root hash")]
+    RootMismatch { expected: Hash, calculated: Hash },
+}
+
+/// to_hash converts a hash from an API response, checking its length
+pub fn to_hash(hash: &[u8]) -> Result<Hash, ProofError> {
+    hash.try_into()
+        .map_err(|_| ProofError::BadHashLength(hash.len()))
+}
+
+/// verify_inclusion checks that audit_path proves leaf_hash is at leaf_index
+/// in the tree of tree_size with the given root, as in RFC 9162 S2.1.3.2.
+pub fn verify_inclusion<T: AsRef<[u8]>>(
+    leaf_hash: &Hash,
+    leaf_index: u64,
+    tree_size: u64,
+    audit_path: &[T],
+    root: &Hash,
+) -> Result<(), ProofError> {
+    if leaf_index >= tree_size {
+        return Err(ProofError::LeafIndexOutOfRange {
+            leaf_index,
+            tree_size,
+        });
+    }
+    let expected = inclusion_proof_size(leaf_index, tree_size);
+    if audit_path.len() != expected {
+        return Err(ProofError::WrongProofSize {
+            expected,
+            actual: audit_path.len(),
+        });
+    }
+
+    let mut fn_ = leaf_index;
+    let mut sn = tree_size - 1;
+    let mut r = *leaf_hash;
+    for p in audit_path {
+        let p = to_hash(p.as_ref())?;
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = node_hash(&p, &r);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            r = node_hash(&r, &p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    debug_assert_eq!(sn, 0, "proof size was checked");
+
+    check_root(root, &r)
+}
+
+/// inclusion_proof_size is the number of hashes in an inclusion proof
+fn inclusion_proof_size(leaf_index: u64, tree_size: u64) -> usize {
+    let inner = 64 - (leaf_index ^ (tree_size - 1)).leading_zeros();
+    let border = (leaf_index >> inner).count_ones();
+    (inner + border) as usize
+}
+
+/// verify_consistency checks that proof shows the tree of second_size with
+/// second_root extends the tree of first_size with first_root, as in RFC 9162 S2.1.4.2.
+pub fn verify_consistency<T: AsRef<[u8]>>(
+    first_size: u64,
+    first_root: &Hash,
+    second_size: u64,
+    second_root: &Hash,
+    proof: &[T],
+) -> Result<(), ProofError> {
+    if second_size < first_size {
+        return Err(ProofError::TreeShrunk {
+            first: first_size,
+            second: second_size,
+        });
+    }
+    if first_size == 0 || first_size == second_size {
+        if !proof.is_empty() {
+            return Err(ProofError::WrongProofSize {
+                expected: 0,
+                actual: proof.len(),
+            });
+        }
+        if first_size == second_size {
+            return check_root(second_root, first_root);
+        }
+        return Ok(());
+    }
+    let expected = consistency_proof_size(first_size, second_size);
+    if proof.len() != expected {
+        return Err(ProofError::WrongProofSize {
+            expected,
+            actual: proof.len(),
+        });
+    }
+
+    let mut path = Vec::with_capacity(proof.len() + 1);
+    if first_size.is_power_of_two() {
+        path.push(*first_root);
+    }
+    for p in proof {
+        path.push(to_hash(p.as_ref())?);
+    }
+
+    let mut fn_ = first_size - 1;
+    let mut sn = second_size - 1;
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    let mut fr = path[0];
+    let mut sr = path[0];
+    for c in &path[1..] {
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    debug_assert_eq!(sn, 0, "proof size was checked");
+
+    check_root(first_root, &fr)?;
+    check_root(second_root, &sr)
+}
+
+/// consistency_proof_size is the number of hashes in a consistency proof,
+/// for 0 < first_size < second_size
+fn consistency_proof_size(first_size: u64, second_size: u64) -> usize {
+    let shift = first_size.trailing_zeros() as usize;
+    let size = inclusion_proof_size(first_size - 1, second_size) - shift;
+    if first_size.is_power_of_two() {
+        size
+    } else {
+        size + 1
+    }
+}
+
+fn check_root(expected: &Hash, calculated: &Hash) -> Result<(), ProofError> {
+    if expected != calculated {
+        return Err(ProofError::RootMismatch {
+            expected: *expected,
+            calculated: *calculated,
+        });
+    }
+    Ok(())
+}
+
+fn finish(ctx: Context) -> Hash {
+    ctx.finish()
+        .as_ref()
+        .try_into()
+        .expect("SHA-256 is 32 bytes")
+}
+
+#[cfg(test)]
+fn from_hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).expect("hex"))
+        .collect()
+}
+
+#[cfg(test)]
+// Test vectors from the RFC 6962 reference implementation's merkle_tree_test.cc
+fn test_leaves() -> Vec<Vec<u8>> {
+    [
+        "",
+        "00",
+        "10",
+        "2021",
+        "3031",
+        "40414243",
+        "5051525354555657",
+        "606162636465666768696a6b6c6d6e6f",
+    ]
+    .iter()
+    .map(|l| from_hex(l))
+    .collect()
+}
+
+#[test]
+fn test_split_point() {
+    for (n, k) in [
+        (2, 1),
+        (3, 2),
+        (4, 2),
+        (5, 4),
+        (8, 4),
+        (9, 8),
+        (1 << 40, 1 << 39),
+    ] {
+        assert_eq!(split_point(n), k, "split_point({n})");
+    }
+}
+
+#[test]
+fn test_tree_hash() {
+    let roots = [
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+        "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+        "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+        "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+        "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+    ];
+    let leaves = test_leaves();
+    for (n, root) in roots.iter().enumerate() {
+        assert_eq!(tree_hash(&leaves[..n]).to_vec(), from_hex(root), "size {n}");
+    }
+}
+
+#[test]
+fn test_leaf_hash() {
+    let leaf = crate::entry::MerkleTreeLeaf::new(
+        1550780035250,
+        crate::entry::LogEntry::X509Entry(crate::entry::Asn1Cert::new(vec![0x30, 0x00])),
+        vec![],
+    );
+    let data = leaf.to_bytes().expect("serializes");
+    assert_eq!(leaf_hash(&leaf).expect("hashes"), leaf_data_hash(&data));
+
+    // The empty leaf from the test vectors
+    assert_eq!(
+        leaf_data_hash(&[]).to_vec(),
+        from_hex("6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d")
+    );
+    assert_eq!(
+        node_hash(&leaf_data_hash(&[]), &leaf_data_hash(&[0])).to_vec(),
+        from_hex("fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125")
+    );
+}
+
+#[cfg(test)]
+/// reference_path is PATH(m, D[n]) from RFC 6962 S2.1.1
+fn reference_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return vec![];
+    }
+    let k = split_point(n as u64) as usize;
+    if m < k {
+        let mut path = reference_path(m, &leaves[..k]);
+        path.push(tree_hash_from_leaf_hashes(&leaves[k..]));
+        path
+    } else {
+        let mut path = reference_path(m - k, &leaves[k..]);
+        path.push(tree_hash_from_leaf_hashes(&leaves[..k]));
+        path
+    }
+}
+
+#[test]
+fn test_verify_inclusion() {
+    let leaves: Vec<Hash> = (0u32..70)
+        .map(|i| leaf_data_hash(&i.to_be_bytes()))
+        .collect();
+    for n in 1..leaves.len() {
+        let root = tree_hash_from_leaf_hashes(&leaves[..n]);
+        for m in 0..n {
+            let path = reference_path(m, &leaves[..n]);
+            assert_eq!(
+                verify_inclusion(&leaves[m], m as u64, n as u64, &path, &root),
+                Ok(()),
+                "leaf {m} in tree of {n}"
+            );
+
+            assert!(verify_inclusion(&leaves[m], m as u64, n as u64, &path, &[0; 32]).is_err());
+            if n > 1 {
+                let other = (m + 1) % n;
+                assert!(
+                    verify_inclusion(&leaves[other], m as u64, n as u64, &path, &root).is_err()
+                );
+                assert!(
+                    verify_inclusion(&leaves[m], other as u64, n as u64, &path, &root).is_err()
+                );
+
+                let mut tampered = path.clone();
+                tampered[0][0] ^= 1;
+                assert!(matches!(
+                    verify_inclusion(&leaves[m], m as u64, n as u64, &tampered, &root),
+                    Err(ProofError::RootMismatch { .. })
+                ));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_verify_inclusion_errors() {
+    let leaves: Vec<Hash> = (0u8..5).map(|i| leaf_data_hash(&[i])).collect();
+    let root = tree_hash_from_leaf_hashes(&leaves);
+    let path = reference_path(2, &leaves);
+
+    assert_eq!(
+        verify_inclusion(&leaves[2], 5, 5, &path, &root),
+        Err(ProofError::LeafIndexOutOfRange {
+            leaf_index: 5,
+            tree_size: 5
+        })
+    );
+
+    let mut long = path.clone();
+    long.push([0; 32]);
+    assert_eq!(
+        verify_inclusion(&leaves[2], 2, 5, &long, &root),
+        Err(ProofError::WrongProofSize {
+            expected: 3,
+            actual: 4
+        })
+    );
+    assert_eq!(
+        verify_inclusion(&leaves[2], 2, 5, &path[..2], &root),
+        Err(ProofError::WrongProofSize {
+            expected: 3,
+            actual: 2
+        })
+    );
+
+    let mut short_hash: Vec<Vec<u8>> = path.iter().map(|h| h.to_vec()).collect();
+    short_hash[1].pop();
+    assert_eq!(
+        verify_inclusion(&leaves[2], 2, 5, &short_hash, &root),
+        Err(ProofError::BadHashLength(31))
+    );
+}
+
+#[cfg(test)]
+/// reference_consistency is PROOF(m, D[n]) from RFC 6962 S2.1.2
+pub(crate) fn reference_consistency(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    fn subproof(m: usize, leaves: &[Hash], complete: bool) -> Vec<Hash> {
+        let n = leaves.len();
+        if m == n {
+            if complete {
+                return vec![];
+            }
+            return vec![tree_hash_from_leaf_hashes(leaves)];
+        }
+        let k = split_point(n as u64) as usize;
+        if m <= k {
+            let mut proof = subproof(m, &leaves[..k], complete);
+            proof.push(tree_hash_from_leaf_hashes(&leaves[k..]));
+            proof
+        } else {
+            let mut proof = subproof(m - k, &leaves[k..], false);
+            proof.push(tree_hash_from_leaf_hashes(&leaves[..k]));
+            proof
+        }
+    }
+    subproof(m, leaves, true)
+}
+
+#[test]
+fn test_verify_consistency() {
+    let leaves: Vec<Hash> = (0u32..40)
+        .map(|i| leaf_data_hash(&i.to_be_bytes()))
+        .collect();
+    for n in 1..leaves.len() {
+        let second_root = tree_hash_from_leaf_hashes(&leaves[..n]);
+        for m in 1..=n {
+            let first_root = tree_hash_from_leaf_hashes(&leaves[..m]);
+            let proof = if m == n {
+                vec![]
+            } else {
+                reference_consistency(m, &leaves[..n])
+            };
+            let (m, n) = (m as u64, n as u64);
+            assert_eq!(
+                verify_consistency(m, &first_root, n, &second_root, &proof),
+                Ok(()),
+                "tree of {m} to tree of {n}"
+            );
+
+            assert!(verify_consistency(m, &[0; 32], n, &second_root, &proof).is_err());
+            assert!(verify_consistency(m, &first_root, n, &[0; 32], &proof).is_err());
+            for tampered_index in 0..proof.len() {
+                let mut tampered = proof.clone();
+                tampered[tampered_index][31] ^= 1;
+                assert!(
+                    verify_consistency(m, &first_root, n, &second_root, &tampered).is_err(),
+                    "tampered hash {tampered_index} from {m} to {n}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_verify_consistency_errors() {
+    let leaves: Vec<Hash> = (0u8..7).map(|i| leaf_data_hash(&[i])).collect();
+    let first_root = tree_hash_from_leaf_hashes(&leaves[..3]);
+    let second_root = tree_hash_from_leaf_hashes(&leaves);
+    let proof = reference_consistency(3, &leaves);
+
+    assert_eq!(
+        verify_consistency(7, &second_root, 3, &first_root, &proof),
+        Err(ProofError::TreeShrunk {
+            first: 7,
+            second: 3
+        })
+    );
+    assert_eq!(
+        verify_consistency(3, &first_root, 7, &second_root, &proof[1..]),
+        Err(ProofError::WrongProofSize {
+            expected: 4,
+            actual: 3
+        })
+    );
+    assert_eq!(
+        verify_consistency(0, &[0; 32], 7, &second_root, &proof),
+        Err(ProofError::WrongProofSize {
+            expected: 0,
+            actual: 4
+        })
+    );
+    assert_eq!(
+        verify_consistency(0, &[0; 32], 7, &second_root, &[] as &[Hash]),
+        Ok(())
+    );
+    assert_eq!(
+        verify_consistency(7, &first_root, 7, &second_root, &[] as &[Hash]),
+        Err(ProofError::RootMismatch {
+            expected: second_root,
+            calculated: first_root
+        })
+    );
+}
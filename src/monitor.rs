@@ -0,0 +1,400 @@
+// Following a log as it grows.
+// Each new STH is checked to be consistent with the last one, and the new
+// entries between them are streamed to the caller.
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use crate::api::GetSthOutput;
+use crate::checkpoint::{Checkpoint, Store};
+use crate::client::{Entry, CT};
+
+/// Errors that stop a Monitor
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Client(#[from] crate::client::Error),
+
+    #[error(transparent)]
+    Checkpoint(#[from] crate::checkpoint::Error),
+
+    #[error("event receiver closed")]
+    Closed,
+}
+
+/// Alert is misbehaviour by the log
+#[derive(Debug)]
+pub enum Alert {
+    /// The log returned a smaller tree than before
+    TreeShrunk {
+        old: GetSthOutput,
+        new: GetSthOutput,
+    },
+    /// The log returned an STH with a bad signature
+    BadSignature(crate::signature::Error),
+    /// The new STH isn't consistent with the old one
+    Inconsistent {
+        old: GetSthOutput,
+        new: GetSthOutput,
+        error: crate::merkle::ProofError,
+    },
+}
+
+/// Event is something a Monitor has seen
+#[derive(Debug)]
+pub enum Event {
+    /// A new entry, in index order
+    Entry(Entry),
+    /// A new STH, consistent with the last one, after all its entries
+    STH(GetSthOutput),
+    Alert(Alert),
+}
+
+/// Monitor polls a log for new STHs.
+/// The CT client should be built with the log's public key, so that STH
+/// signatures are checked.
+pub struct Monitor {
+    ct: CT,
+    interval: Duration,
+    sth: Option<GetSthOutput>,
+    // Index of the next entry to send, or None to start at the first STH
+    next_index: Option<u64>,
+    // Tree size the log has been proven consistent up to
+    verified_size: u64,
+    store: Option<(Arc<dyn Store + Send + Sync>, String)>,
+    checkpoint_every: u64,
+}
+
+impl Monitor {
+    /// Monitor starting from the log's current STH, without entries before it
+    pub fn new(ct: CT, interval: Duration) -> Monitor {
+        Monitor {
+            ct,
+            interval,
+            sth: None,
+            next_index: None,
+            verified_size: 0,
+            store: None,
+            checkpoint_every: 256,
+        }
+    }
+
+    /// Monitor which saves a checkpoint for log in store as it goes, and
+    /// resumes from the checkpoint there if there is one.
+    /// A checkpoint is saved after every verified STH, and every 256
+    /// entries sent.
+    pub fn with_store(
+        ct: CT,
+        interval: Duration,
+        store: Arc<dyn Store + Send + Sync>,
+        log: &str,
+    ) -> Result<Monitor, Error> {
+        let checkpoint = store.load(log)?;
+        let mut monitor = Monitor::new(ct, interval);
+        if let Some(checkpoint) = checkpoint {
+            monitor.sth = checkpoint.sth;
+            monitor.next_index = Some(checkpoint.next_index);
+            monitor.verified_size = checkpoint.verified_size;
+        }
+        monitor.store = Some((store, log.to_string()));
+        Ok(monitor)
+    }
+
+    /// Save a checkpoint every checkpoint_every entries sent.
+    /// Entries since the last checkpoint are sent again after a restart.
+    pub fn checkpoint_every(mut self, checkpoint_every: u64) -> Monitor {
+        self.checkpoint_every = checkpoint_every.max(1);
+        self
+    }
+
+    /// The last verified STH
+    pub fn sth(&self) -> Option<&GetSthOutput> {
+        self.sth.as_ref()
+    }
+
+    /// The tree size the log has been proven consistent up to, or 0 if no
+    /// STH has been checked against an earlier one yet
+    pub fn verified_size(&self) -> u64 {
+        self.verified_size
+    }
+
+    /// run polls the log every interval, until events is closed or
+    /// there's an error talking to the log
+    pub async fn run(mut self, events: mpsc::Sender<Event>) -> Result<(), Error> {
+        while !events.is_closed() {
+            match self.poll(&events).await {
+                Ok(()) => {}
+                Err(Error::Closed) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+        Ok(())
+    }
+
+    /// poll fetches the latest STH once, and sends any new entries and the
+    /// STH to events. Misbehaviour is sent as an Alert, and the STH ignored.
+    /// Entries left over from an interrupted poll are sent first.
+    pub async fn poll(&mut self, events: &mpsc::Sender<Event>) -> Result<(), Error> {
+        let new = match self.ct.get_sth().await {
+            Ok(sth) => sth,
+            Err(crate::client::Error::Signature(e)) => {
+                return send(events, Event::Alert(Alert::BadSignature(e))).await;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let grown = match self.sth.clone() {
+            None => true,
+            Some(old) => {
+                if new.tree_size < old.tree_size {
+                    return send(events, Event::Alert(Alert::TreeShrunk { old, new })).await;
+                }
+                match self.ct.verify_consistency(&old, &new).await {
+                    Ok(()) => self.verified_size = self.verified_size.max(new.tree_size),
+                    Err(crate::client::Error::NotAppendOnly(error)) => {
+                        let alert = Alert::Inconsistent { old, new, error };
+                        return send(events, Event::Alert(alert)).await;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+                new.tree_size > old.tree_size
+            }
+        };
+        if grown {
+            // The STH is verified before its entries are sent, so that a
+            // restart carries on sending them
+            self.sth = Some(new);
+            self.next_index.get_or_insert(self.tree_size());
+            self.save()?;
+        }
+        let sth = self.sth.clone().expect("set above");
+        let mut next_index = self.next_index.expect("set above");
+        if !grown && next_index >= sth.tree_size {
+            return Ok(());
+        }
+
+        let mut entries = self.ct.entries(next_index..sth.tree_size);
+        let mut unsaved = 0;
+        loop {
+            let entry = match entries.try_next().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    // So that entries already sent aren't sent again
+                    self.save()?;
+                    return Err(e.into());
+                }
+            };
+            send(events, Event::Entry(entry)).await?;
+            next_index += 1;
+            self.next_index = Some(next_index);
+            unsaved += 1;
+            if unsaved == self.checkpoint_every {
+                self.save()?;
+                unsaved = 0;
+            }
+        }
+        drop(entries);
+        self.save()?;
+        send(events, Event::STH(sth)).await
+    }
+
+    fn tree_size(&self) -> u64 {
+        self.sth.as_ref().map_or(0, |sth| sth.tree_size)
+    }
+
+    /// save records the verified STH and the next entry to send
+    fn save(&self) -> Result<(), Error> {
+        if let Some((store, log)) = &self.store {
+            let checkpoint = Checkpoint {
+                next_index: self.next_index.unwrap_or(0),
+                sth: self.sth.clone(),
+                verified_size: self.verified_size,
+            };
+            store.save(log, &checkpoint)?;
+        }
+        Ok(())
+    }
+}
+
+async fn send(events: &mpsc::Sender<Event>, event: Event) -> Result<(), Error> {
+    events.send(event).await.map_err(|_| Error::Closed)
+}
+
+#[cfg(test)]
+/// test_log serves the test log, at the tree size in size, or with the
+/// STH in sth if it's set
+fn test_log(
+    size: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    sth: std::sync::Arc<std::sync::Mutex<Option<GetSthOutput>>>,
+) -> impl Fn(&crate::testlog::Request) -> crate::testlog::Response {
+    use crate::testlog::{get_entries, get_sth_consistency, Response};
+    move |request| {
+        let size = size.load(std::sync::atomic::Ordering::SeqCst);
+        match request.path.as_str() {
+            "/ct/v1/get-sth" => match &*sth.lock().unwrap() {
+                Some(sth) => Response::sth(sth),
+                None => Response::sth(&crate::testlog::sth(size)),
+            },
+            "/ct/v1/get-sth-consistency" => get_sth_consistency(request),
+            "/ct/v1/get-entries" => get_entries(request, size, 2),
+            _ => Response::status(404, "not found"),
+        }
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_monitor() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    let size = Arc::new(AtomicU64::new(3));
+    let bad_sth = Arc::new(Mutex::new(None));
+    let url = crate::testlog::serve(test_log(size.clone(), bad_sth.clone())).await;
+    let key = crate::signature::PublicKey::from_pem(crate::signature::TEST_EC_PUB.as_bytes())
+        .expect("parses");
+    let ct = CT::with_public_key(&url, key).expect("parses");
+    let store = Arc::new(crate::checkpoint::MemoryStore::new());
+
+    let mut monitor =
+        Monitor::with_store(ct, Duration::from_millis(1), store.clone(), &url).expect("starts");
+    let (tx, mut rx) = mpsc::channel(100);
+
+    // The first STH is trusted as is
+    monitor.poll(&tx).await.expect("polls");
+    assert!(matches!(rx.try_recv(), Ok(Event::STH(sth)) if sth.tree_size == 3));
+    assert!(rx.try_recv().is_err());
+    let checkpoint = store.load(&url).expect("loads").expect("saved");
+    assert_eq!(checkpoint.verified_size, 0);
+
+    // Growing streams the new entries, then the STH
+    size.store(8, Ordering::SeqCst);
+    monitor.poll(&tx).await.expect("polls");
+    for index in 3..8 {
+        assert!(matches!(rx.try_recv(), Ok(Event::Entry(e)) if e.index == index));
+    }
+    assert!(matches!(rx.try_recv(), Ok(Event::STH(sth)) if sth.tree_size == 8));
+    monitor.poll(&tx).await.expect("polls");
+    assert!(rx.try_recv().is_err());
+
+    // Shrinking, a fork and a bad signature are alerts
+    size.store(5, Ordering::SeqCst);
+    monitor.poll(&tx).await.expect("polls");
+    assert!(matches!(
+        rx.try_recv(),
+        Ok(Event::Alert(Alert::TreeShrunk { old, new })) if old.tree_size == 8 && new.tree_size == 5
+    ));
+    size.store(9, Ordering::SeqCst);
+    let mut fork = crate::signature::test_sth(crate::signature::TEST_EC_KEY, 9, 1, [7; 32]);
+    *bad_sth.lock().unwrap() = Some(fork.clone());
+    monitor.poll(&tx).await.expect("polls");
+    assert!(matches!(
+        rx.try_recv(),
+        Ok(Event::Alert(Alert::Inconsistent { .. }))
+    ));
+    fork.timestamp += 1;
+    *bad_sth.lock().unwrap() = Some(fork);
+    monitor.poll(&tx).await.expect("polls");
+    assert!(matches!(
+        rx.try_recv(),
+        Ok(Event::Alert(Alert::BadSignature(
+            crate::signature::Error::BadSignature
+        )))
+    ));
+    assert_eq!(monitor.sth().map(|s| s.tree_size), Some(8));
+
+    // A restarted monitor resumes from the saved STH
+    *bad_sth.lock().unwrap() = None;
+    let ct = CT::new(&url).expect("parses");
+    let checkpoint = store.load(&url).expect("loads").expect("saved");
+    assert_eq!(checkpoint.next_index, 8);
+    assert_eq!(checkpoint.verified_size, 8);
+    let monitor = Monitor::with_store(ct, Duration::from_millis(1), store, &url).expect("starts");
+    assert_eq!(monitor.sth().map(|s| s.tree_size), Some(8));
+    assert_eq!(monitor.verified_size(), 8);
+    let (tx, mut rx) = mpsc::channel(1);
+    let running = tokio::spawn(monitor.run(tx));
+    assert!(matches!(rx.recv().await, Some(Event::Entry(e)) if e.index == 8));
+    assert!(matches!(rx.recv().await, Some(Event::STH(sth)) if sth.tree_size == 9));
+    drop(rx);
+    running.await.expect("joins").expect("stops cleanly");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_monitor_resume() {
+    use crate::testlog::{get_entries, get_sth_consistency, Response};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // get-entries fails from fail_from on
+    let size = Arc::new(AtomicU64::new(3));
+    let fail_from = Arc::new(AtomicU64::new(u64::MAX));
+    let (log_size, log_fail_from) = (size.clone(), fail_from.clone());
+    let url = crate::testlog::serve(move |request| {
+        let size = log_size.load(Ordering::SeqCst);
+        match request.path.as_str() {
+            "/ct/v1/get-sth" => Response::sth(&crate::testlog::sth(size)),
+            "/ct/v1/get-sth-consistency" => get_sth_consistency(request),
+            "/ct/v1/get-entries"
+                if request.param("start") >= log_fail_from.load(Ordering::SeqCst) =>
+            {
+                Response::status(500, "unavailable")
+            }
+            "/ct/v1/get-entries" => get_entries(request, size, 2),
+            _ => Response::status(404, "not found"),
+        }
+    })
+    .await;
+    let dir = std::env::temp_dir().join(format!("sect-monitor-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("creates");
+    let path = dir.join("checkpoints.json");
+    let _ = std::fs::remove_file(&path);
+    let start = |url: &str| {
+        let store = Arc::new(crate::checkpoint::JsonFileStore::new(&path));
+        let ct = CT::new(url).expect("parses");
+        Monitor::with_store(ct, Duration::from_millis(1), store, url)
+            .expect("starts")
+            .checkpoint_every(2)
+    };
+
+    let mut monitor = start(&url);
+    let (tx, mut rx) = mpsc::channel(100);
+    monitor.poll(&tx).await.expect("polls");
+    assert!(matches!(rx.try_recv(), Ok(Event::STH(sth)) if sth.tree_size == 3));
+    assert_eq!(monitor.verified_size(), 0);
+
+    // The log fails partway through sending 3..9
+    size.store(9, Ordering::SeqCst);
+    fail_from.store(7, Ordering::SeqCst);
+    assert!(monitor.poll(&tx).await.is_err());
+    for index in 3..7 {
+        assert!(matches!(rx.try_recv(), Ok(Event::Entry(e)) if e.index == index));
+    }
+    assert!(rx.try_recv().is_err());
+    drop(monitor);
+
+    // A new monitor carries on from the last checkpoint on disk
+    let checkpoint = crate::checkpoint::JsonFileStore::new(&path)
+        .load(&url)
+        .expect("loads")
+        .expect("saved");
+    assert_eq!(checkpoint.next_index, 7);
+    // 3..9 was proven consistent before its entries were fetched
+    assert_eq!(checkpoint.verified_size, 9);
+    fail_from.store(u64::MAX, Ordering::SeqCst);
+    let mut monitor = start(&url);
+    assert_eq!(monitor.sth().map(|s| s.tree_size), Some(9));
+    assert_eq!(monitor.verified_size(), 9);
+    monitor.poll(&tx).await.expect("polls");
+    for index in 7..9 {
+        assert!(matches!(rx.try_recv(), Ok(Event::Entry(e)) if e.index == index));
+    }
+    assert!(matches!(rx.try_recv(), Ok(Event::STH(sth)) if sth.tree_size == 9));
+    monitor.poll(&tx).await.expect("polls");
+    assert!(rx.try_recv().is_err());
+    std::fs::remove_dir_all(&dir).expect("removes");
+}
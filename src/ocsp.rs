@@ -0,0 +1,213 @@
+// SCTs stapled in an OCSP response, S3.3.
+// Only enough of RFC 6960 is read to find the SCT list extension in the
+// singleExtensions of a basic response.
+use thiserror::Error;
+
+use crate::der::{self, Tlv};
+use crate::sct::SignedCertificateTimestampList;
+
+// OCSP SCT list extension, 1.3.6.1.4.1.11129.2.4.5
+const OID_CT_OCSP_SCT_LIST: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x05];
+// id-pkix-ocsp-basic, 1.3.6.1.5.5.7.48.1.1
+const OID_OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+const ENUMERATED: u8 = 0x0a;
+
+/// Errors reading SCTs from an OCSP response
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("malformed OCSP response: {0}")]
+    Response(#[from] der::Error),
+
+    #[error("OCSP response status {0} isn't successful")]
+    Status(u8),
+
+    #[error("OCSP response has no response bytes")]
+    NoResponseBytes,
+
+    #[error("OCSP response isn't a basic response")]
+    UnsupportedResponseType,
+
+    #[error("OCSP response has no SCT list extension")]
+    NoSCTList,
+
+    #[error("malformed SCT list: {0}")]
+    SCT(#[from] crate::sct::Error),
+}
+
+/// scts_from_response decodes the SCT list stapled in a DER OCSPResponse.
+/// The first SingleResponse with an SCT list extension is used.
+pub fn scts_from_response(response: &[u8]) -> Result<SignedCertificateTimestampList, Error> {
+    let mut reader = Tlv::parse_tag(response, der::SEQUENCE)?.reader();
+    match reader.read_tag(ENUMERATED)?.value {
+        [0] => {}
+        [status] => return Err(Error::Status(*status)),
+        _ => return Err(der::Error::Invalid("OCSP response status").into()),
+    }
+    let response_bytes = reader
+        .read_optional(der::context(0))?
+        .ok_or(Error::NoResponseBytes)?;
+    reader.finish()?;
+
+    let mut outer = response_bytes.reader();
+    let mut response_bytes = outer.read_tag(der::SEQUENCE)?.reader();
+    outer.finish()?;
+    if response_bytes.read_tag(der::OID)?.value != OID_OCSP_BASIC {
+        return Err(Error::UnsupportedResponseType);
+    }
+    let basic = response_bytes.read_tag(der::OCTET_STRING)?.value;
+    response_bytes.finish()?;
+
+    // BasicOCSPResponse, of which only tbsResponseData is needed
+    let mut basic = Tlv::parse_tag(basic, der::SEQUENCE)?.reader();
+    let mut response_data = basic.read_tag(der::SEQUENCE)?.reader();
+    response_data.read_optional(der::context(0))?; // version
+    response_data.read()?; // responderID
+    response_data.read()?; // producedAt
+    let mut responses = response_data.read_tag(der::SEQUENCE)?.reader();
+
+    while responses.peek_tag().is_some() {
+        let mut single = responses.read_tag(der::SEQUENCE)?.reader();
+        single.read_tag(der::SEQUENCE)?; // certID
+        single.read()?; // certStatus
+        single.read()?; // thisUpdate
+        single.read_optional(der::context(0))?; // nextUpdate
+        let extensions = match single.read_optional(der::context(1))? {
+            Some(extensions) => crate::x509::parse_extensions(&extensions)?,
+            None => continue,
+        };
+        single.finish()?;
+        if let Some(extension) = extensions.iter().find(|e| e.oid == OID_CT_OCSP_SCT_LIST) {
+            return Ok(SignedCertificateTimestampList::from_extension_value(
+                extension.value,
+            )?);
+        }
+    }
+    Err(Error::NoSCTList)
+}
+
+#[cfg(test)]
+/// test_response builds a successful OCSPResponse with one SingleResponse,
+/// which has the given singleExtensions
+fn test_response(single_extensions: &[&[u8]]) -> Vec<u8> {
+    let generalized_time = b"\x18\x0f20260101000000Z";
+    let cert_id = der::encode(
+        der::SEQUENCE,
+        &[
+            &der::encode(
+                der::SEQUENCE,
+                &[
+                    // sha1
+                    &der::encode(der::OID, &[&[0x2b, 0x0e, 0x03, 0x02, 0x1a]]),
+                    &[der::NULL, 0],
+                ],
+            ),
+            &der::encode(der::OCTET_STRING, &[&[1; 20]]),
+            &der::encode(der::OCTET_STRING, &[&[2; 20]]),
+            &der::encode(der::INTEGER, &[&[0x03, 0xe8]]),
+        ],
+    );
+    let extensions = der::encode(
+        der::context(1),
+        &[&der::encode(der::SEQUENCE, single_extensions)],
+    );
+    let mut single: Vec<&[u8]> = vec![&cert_id, &[0x80, 0x00], generalized_time];
+    if !single_extensions.is_empty() {
+        single.push(&extensions);
+    }
+    let single = der::encode(der::SEQUENCE, &single);
+    let response_data = der::encode(
+        der::SEQUENCE,
+        &[
+            &der::encode(0xa2, &[&der::encode(der::OCTET_STRING, &[&[3; 20]])]),
+            generalized_time,
+            &der::encode(der::SEQUENCE, &[&single]),
+        ],
+    );
+    let basic = der::encode(
+        der::SEQUENCE,
+        &[
+            &response_data,
+            // ecdsa-with-SHA256
+            &der::encode(
+                der::SEQUENCE,
+                &[&der::encode(
+                    der::OID,
+                    &[&[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]],
+                )],
+            ),
+            &[der::BIT_STRING, 0x01, 0x00],
+        ],
+    );
+    let response_bytes = der::encode(
+        der::SEQUENCE,
+        &[
+            &der::encode(der::OID, &[OID_OCSP_BASIC]),
+            &der::encode(der::OCTET_STRING, &[&basic]),
+        ],
+    );
+    der::encode(
+        der::SEQUENCE,
+        &[
+            &[ENUMERATED, 0x01, 0x00],
+            &der::encode(der::context(0), &[&response_bytes]),
+        ],
+    )
+}
+
+#[test]
+fn test_scts_from_response() {
+    // The OCSP extension has the same value as the certificate extension
+    let cert = include_bytes!("../testdata/cert-scts.der");
+    let embedded =
+        crate::embedded::EmbeddedSCTs::from_certificate(cert, include_bytes!("../testdata/ca.der"))
+            .expect("extracts");
+    let tbs = crate::x509::TbsCertificate::from_certificate(cert).expect("parses");
+    let value = tbs
+        .extension(crate::x509::OID_CT_SCT_LIST)
+        .expect("has SCTs")
+        .value;
+    let extension = der::encode(
+        der::SEQUENCE,
+        &[
+            &der::encode(der::OID, &[OID_CT_OCSP_SCT_LIST]),
+            &der::encode(der::OCTET_STRING, &[value]),
+        ],
+    );
+    // An OCSP nonce, 1.3.6.1.5.5.7.48.1.2
+    let nonce = der::encode(
+        der::SEQUENCE,
+        &[
+            &der::encode(
+                der::OID,
+                &[&[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x02]],
+            ),
+            &der::encode(der::OCTET_STRING, &[&[der::OCTET_STRING, 1, 7]]),
+        ],
+    );
+
+    let response = test_response(&[&nonce, &extension]);
+    let scts = scts_from_response(&response).expect("decodes");
+    assert_eq!(scts.scts(), embedded.scts());
+
+    assert!(matches!(
+        scts_from_response(&test_response(&[&nonce])),
+        Err(Error::NoSCTList)
+    ));
+    assert!(matches!(
+        scts_from_response(&test_response(&[])),
+        Err(Error::NoSCTList)
+    ));
+    // tryLater
+    assert!(matches!(
+        scts_from_response(&[der::SEQUENCE, 0x03, ENUMERATED, 0x01, 0x03]),
+        Err(Error::Status(3))
+    ));
+    assert!(matches!(
+        scts_from_response(&[der::SEQUENCE, 0x03, ENUMERATED, 0x01, 0x00]),
+        Err(Error::NoResponseBytes)
+    ));
+    assert!(matches!(
+        scts_from_response(&response[..response.len() - 1]),
+        Err(Error::Response(_))
+    ));
+}
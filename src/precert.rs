@@ -0,0 +1,186 @@
+// Preparing a precertificate chain for add-pre-chain, S3.1 and S3.2.
+// The logged TBSCertificate has the poison removed, and if the precertificate
+// was issued by a Precertificate Signing Certificate, it's rewritten to look
+// like it was issued by the CA that issued the signing certificate.
+use thiserror::Error;
+
+use crate::der;
+use crate::entry::PreCert;
+use crate::x509::{self, TbsCertificate};
+
+/// Errors preparing a precertificate chain
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("empty precertificate chain")]
+    EmptyChain,
+
+    #[error("precertificate chain has no issuer")]
+    MissingIssuer,
+
+    #[error("precertificate signing certificate has no issuer in the chain")]
+    MissingSigningCertificateIssuer,
+
+    #[error("malformed certificate: {0}")]
+    Certificate(#[from] der::Error),
+
+    #[error("precertificate has no CT poison extension")]
+    NoPoison,
+
+    #[error("CT poison extension isn't critical")]
+    PoisonNotCritical,
+
+    #[error("CT poison extension isn't NULL")]
+    BadPoison,
+}
+
+/// prepare builds the PreCert for a chain submitted to add-pre-chain.
+/// chain[0] is the precertificate and chain[1] its issuer. If chain[1] is a
+/// Precertificate Signing Certificate, chain[2] is the CA that issued it.
+pub fn prepare(chain: &[Vec<u8>]) -> Result<PreCert, Error> {
+    let precert = chain.first().ok_or(Error::EmptyChain)?;
+    let issuer = chain.get(1).ok_or(Error::MissingIssuer)?;
+
+    let mut tbs = TbsCertificate::from_certificate(precert)?;
+    let poison = tbs
+        .remove_extension(x509::OID_CT_POISON)
+        .ok_or(Error::NoPoison)?;
+    if !poison.critical {
+        return Err(Error::PoisonNotCritical);
+    }
+    if poison.value != [der::NULL, 0] {
+        return Err(Error::BadPoison);
+    }
+
+    let issuer_tbs = TbsCertificate::from_certificate(issuer)?;
+    if !is_precert_signing_certificate(&issuer_tbs)? {
+        return Ok(PreCert::new(x509::issuer_key_hash(issuer)?, tbs.encode()));
+    }
+
+    // S3.1: the issuer is that of the signing certificate, and so is the
+    // AKI, if the precertificate has one
+    let ca = chain.get(2).ok_or(Error::MissingSigningCertificateIssuer)?;
+    tbs.set_issuer(issuer_tbs.issuer());
+    if tbs.extension(x509::OID_AUTHORITY_KEY_IDENTIFIER).is_some() {
+        match issuer_tbs.extension(x509::OID_AUTHORITY_KEY_IDENTIFIER) {
+            Some(aki) => {
+                tbs.replace_extension(*aki);
+            }
+            None => {
+                tbs.remove_extension(x509::OID_AUTHORITY_KEY_IDENTIFIER);
+            }
+        }
+    }
+    Ok(PreCert::new(x509::issuer_key_hash(ca)?, tbs.encode()))
+}
+
+/// is_precert_signing_certificate checks the extended key usage of an issuer
+/// for the Precertificate Signing Certificate OID
+fn is_precert_signing_certificate(tbs: &TbsCertificate) -> Result<bool, Error> {
+    let eku = match tbs.extension(x509::OID_EXTENDED_KEY_USAGE) {
+        Some(eku) => eku,
+        None => return Ok(false),
+    };
+    let mut reader = der::Tlv::parse_tag(eku.value, der::SEQUENCE)?.reader();
+    while reader.peek_tag().is_some() {
+        if reader.read_tag(der::OID)?.value == x509::OID_CT_PRECERT_SIGNING {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[test]
+fn test_prepare() {
+    let ca = include_bytes!("../testdata/ca.der").to_vec();
+    let cert = include_bytes!("../testdata/cert.der").to_vec();
+    let precert = include_bytes!("../testdata/precert.der").to_vec();
+
+    let prepared = prepare(&[precert.clone(), ca.clone()]).expect("prepares");
+    assert_eq!(
+        *prepared.issuer_key_hash(),
+        x509::issuer_key_hash(&ca).expect("hashes")
+    );
+    let tbs = TbsCertificate::parse(prepared.tbs_certificate()).expect("parses");
+    assert!(tbs.extension(x509::OID_CT_POISON).is_none());
+
+    assert_eq!(prepare(&[]).unwrap_err(), Error::EmptyChain);
+    assert_eq!(
+        prepare(std::slice::from_ref(&precert)).unwrap_err(),
+        Error::MissingIssuer
+    );
+    assert_eq!(
+        prepare(&[cert.clone(), ca.clone()]).unwrap_err(),
+        Error::NoPoison
+    );
+    assert!(matches!(
+        prepare(&[precert[..50].to_vec(), ca.clone()]),
+        Err(Error::Certificate(_))
+    ));
+}
+
+#[test]
+fn test_prepare_bad_poison() {
+    let ca = include_bytes!("../testdata/ca.der").to_vec();
+    let precert = include_bytes!("../testdata/precert.der").to_vec();
+    // The poison extension, critical with a NULL value
+    let poison = der::encode(
+        der::SEQUENCE,
+        &[
+            &der::encode(der::OID, &[x509::OID_CT_POISON]),
+            &[der::BOOLEAN, 1, 0xff],
+            &[der::OCTET_STRING, 2, der::NULL, 0],
+        ],
+    );
+    let at = precert
+        .windows(poison.len())
+        .position(|w| w == poison)
+        .expect("has poison");
+
+    // Same length replacements keep the rest of the encoding valid
+    let mut not_critical = precert.clone();
+    not_critical[at + poison.len() - 5] = 0;
+    assert_eq!(
+        prepare(&[not_critical, ca.clone()]).unwrap_err(),
+        Error::PoisonNotCritical
+    );
+    let mut not_null = precert.clone();
+    not_null[at + poison.len() - 2] = der::BOOLEAN;
+    assert_eq!(
+        prepare(&[not_null, ca.clone()]).unwrap_err(),
+        Error::BadPoison
+    );
+}
+
+#[test]
+fn test_prepare_signing_certificate() {
+    let ca = include_bytes!("../testdata/ca.der").to_vec();
+    let psc = include_bytes!("../testdata/psc.der").to_vec();
+    let psc_precert = include_bytes!("../testdata/psc-precert.der").to_vec();
+    let precert = include_bytes!("../testdata/precert.der").to_vec();
+
+    assert!(is_precert_signing_certificate(
+        &TbsCertificate::from_certificate(&psc).expect("parses")
+    )
+    .expect("has EKU"));
+    assert!(!is_precert_signing_certificate(
+        &TbsCertificate::from_certificate(&precert).expect("parses")
+    )
+    .expect("has EKU"));
+
+    // psc-precert.der is precert.der issued through a signing certificate,
+    // so once rewritten it's logged the same way
+    let prepared = prepare(&[psc_precert.clone(), psc.clone(), ca.clone()]).expect("prepares");
+    assert_eq!(prepared, prepare(&[precert, ca.clone()]).expect("prepares"));
+    let tbs = TbsCertificate::parse(prepared.tbs_certificate()).expect("parses");
+    assert_eq!(
+        tbs.issuer(),
+        TbsCertificate::from_certificate(&ca)
+            .expect("parses")
+            .issuer()
+    );
+
+    assert_eq!(
+        prepare(&[psc_precert, psc]).unwrap_err(),
+        Error::MissingSigningCertificateIssuer
+    );
+}
@@ -0,0 +1,152 @@
+// Client-side rate limiting, so a client stays within a log's published
+// limits. A token is only taken once it's available, so a request that
+// stops waiting, e.g. because it was cancelled, doesn't use one up.
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// TokenBucket allows rate requests a second on average, in bursts of up to
+/// burst requests. Clones share the same tokens.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Arc<Mutex<BucketState>>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// TokenBucket which starts full.
+    /// Panics unless rate is positive.
+    pub fn new(rate: f64, burst: u32) -> TokenBucket {
+        assert!(rate > 0.0, "rate must be positive");
+        let burst = f64::from(burst.max(1));
+        TokenBucket {
+            rate,
+            burst,
+            state: Arc::new(Mutex::new(BucketState {
+                tokens: burst,
+                updated: Instant::now(),
+            })),
+        }
+    }
+
+    /// acquire waits until a request may be made
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_take() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// acquire_blocking is acquire for use outside an async runtime
+    pub fn acquire_blocking(&self) {
+        while let Err(wait) = self.try_take() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// try_take takes a token if there is one, or returns how long until
+    /// there will be
+    fn try_take(&self) -> Result<(), Duration> {
+        // Tolerates rounding in the refill, so a full wait is always enough
+        const ALMOST_ONE: f64 = 1.0 - 1e-9;
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.updated).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.burst);
+        state.updated = now;
+        if state.tokens >= ALMOST_ONE {
+            state.tokens = (state.tokens - 1.0).max(0.0);
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - state.tokens) / self.rate))
+        }
+    }
+}
+
+/// RateLimiter limits the requests made to a log, with separate budgets
+/// for reads and for writes (add-chain and add-pre-chain).
+/// Clones share the same budgets, as do CT clients given clones.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    reads: Option<TokenBucket>,
+    writes: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    /// RateLimiter which doesn't limit anything
+    pub fn new() -> RateLimiter {
+        RateLimiter::default()
+    }
+
+    /// Limit reads to bucket
+    pub fn reads(mut self, bucket: TokenBucket) -> RateLimiter {
+        self.reads = Some(bucket);
+        self
+    }
+
+    /// Limit writes to bucket
+    pub fn writes(mut self, bucket: TokenBucket) -> RateLimiter {
+        self.writes = Some(bucket);
+        self
+    }
+
+    /// acquire waits until a read, or a write, may be made
+    pub async fn acquire(&self, write: bool) {
+        let bucket = if write { &self.writes } else { &self.reads };
+        if let Some(bucket) = bucket {
+            bucket.acquire().await;
+        }
+    }
+
+    /// acquire_blocking is acquire for use outside an async runtime
+    pub fn acquire_blocking(&self, write: bool) {
+        let bucket = if write { &self.writes } else { &self.reads };
+        if let Some(bucket) = bucket {
+            bucket.acquire_blocking();
+        }
+    }
+}
+
+#[test]
+fn test_try_take() {
+    let bucket = TokenBucket::new(10.0, 2);
+    assert_eq!(bucket.try_take(), Ok(()));
+    assert_eq!(bucket.clone().try_take(), Ok(()));
+    // Clones share tokens, and waiting doesn't take one
+    for _ in 0..2 {
+        let wait = bucket.clone().try_take().expect_err("empty");
+        assert!(wait > Duration::from_millis(90) && wait <= Duration::from_millis(100));
+    }
+}
+
+#[cfg(test)]
+#[tokio::test(start_paused = true)]
+async fn test_rate_limiter() {
+    let limiter = RateLimiter::new().reads(TokenBucket::new(100.0, 2));
+    let start = Instant::now();
+    // Writes and the first burst of reads aren't limited
+    for _ in 0..10 {
+        limiter.acquire(true).await;
+    }
+    limiter.acquire(false).await;
+    limiter.clone().acquire(false).await;
+    assert_eq!(start.elapsed(), Duration::ZERO);
+    // Then reads are 10ms apart
+    for _ in 0..3 {
+        limiter.clone().acquire(false).await;
+    }
+    assert_eq!(start.elapsed(), Duration::from_millis(30));
+    // A cancelled wait doesn't hold back the next read
+    let cancelled = tokio::time::timeout(Duration::from_millis(5), limiter.acquire(false)).await;
+    assert!(cancelled.is_err());
+    limiter.clone().acquire(false).await;
+    assert_eq!(start.elapsed(), Duration::from_millis(40));
+}
@@ -0,0 +1,131 @@
+// Resolving the log ID in an SCT to the log that issued it.
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::loglist::LogList;
+use crate::signature::PublicKey;
+
+/// LogInfo is what's known about a log
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogInfo {
+    pub url: String,
+    pub key: PublicKey,
+    pub description: Option<String>,
+    pub operator: Option<String>,
+}
+
+impl LogInfo {
+    pub fn new(url: &str, key: PublicKey) -> LogInfo {
+        LogInfo {
+            url: url.to_string(),
+            key,
+            description: None,
+            operator: None,
+        }
+    }
+
+    /// The log ID, SHA-256 of the log's public key
+    pub fn log_id(&self) -> [u8; 32] {
+        self.key.log_id()
+    }
+
+    /// A CT client for this log, which verifies responses against its key
+    #[cfg(feature = "client")]
+    pub fn client(&self) -> Result<crate::client::CT, url::ParseError> {
+        crate::client::CT::with_public_key(&self.url, self.key.clone())
+    }
+}
+
+/// Registry maps log IDs to logs
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    logs: HashMap<[u8; 32], LogInfo>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Registry of every log in a log list
+    pub fn from_log_list(list: &LogList) -> Result<Registry, crate::loglist::Error> {
+        let mut registry = Registry::new();
+        for operator in &list.operators {
+            for log in &operator.logs {
+                registry.insert(LogInfo {
+                    url: log.url.clone(),
+                    key: log.public_key()?,
+                    description: log.description.clone(),
+                    operator: Some(operator.name.clone()),
+                });
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Registry of every log in a log_list.json file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Registry, crate::loglist::Error> {
+        Registry::from_log_list(&LogList::from_file(path)?)
+    }
+
+    /// Add a log, returning the log it replaced if one had the same ID
+    pub fn insert(&mut self, log: LogInfo) -> Option<LogInfo> {
+        self.logs.insert(log.log_id(), log)
+    }
+
+    /// The log with the given log ID, such as SignedCertificateTimestamp::log_id
+    pub fn lookup(&self, log_id: &[u8; 32]) -> Option<&LogInfo> {
+        self.logs.get(log_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.logs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.logs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LogInfo> {
+        self.logs.values()
+    }
+}
+
+#[test]
+fn test_registry() {
+    use crate::signature::{TEST_EC_PUB, TEST_RSA_PUB};
+
+    let registry = Registry::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/testdata/log_list.json"
+    ))
+    .expect("loads");
+    assert_eq!(registry.len(), 3);
+
+    let ec_key = PublicKey::from_pem(TEST_EC_PUB.as_bytes()).expect("parses");
+    let log = registry.lookup(&ec_key.log_id()).expect("found");
+    assert_eq!(log.url, "https://ct.example.com/ec2026/");
+    assert_eq!(log.key, ec_key);
+    assert_eq!(log.operator.as_deref(), Some("Sect Test Operator"));
+    assert_eq!(log.description.as_deref(), Some("Sect Test 'EC2026' log"));
+    #[cfg(feature = "client")]
+    log.client().expect("builds");
+    assert!(registry.lookup(&[0; 32]).is_none());
+
+    // Logs can be added by hand too
+    let mut registry = Registry::new();
+    assert!(registry.is_empty());
+    let rsa_key = PublicKey::from_pem(TEST_RSA_PUB.as_bytes()).expect("parses");
+    assert_eq!(
+        registry.insert(LogInfo::new("log.example.org/rsa", rsa_key.clone())),
+        None
+    );
+    assert!(registry
+        .insert(LogInfo::new("https://log.example.org/rsa", rsa_key.clone()))
+        .is_some());
+    assert_eq!(registry.len(), 1);
+    assert_eq!(
+        registry.lookup(&rsa_key.log_id()).map(|l| l.url.as_str()),
+        Some("https://log.example.org/rsa")
+    );
+}
@@ -0,0 +1,188 @@
+// When and how long to wait before retrying a failed request.
+use std::time::{Duration, SystemTime};
+
+use ring::rand::SecureRandom;
+
+/// RetryPolicy decides which failed requests to the log are retried.
+/// Requests that change the log, add-chain and add-pre-chain, are only
+/// retried when the log can't have acted on them: a 429 or 503 status, or a
+/// failure to connect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first. 1 never retries.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each retry after it
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts, unless Retry-After asks for longer
+    pub max_backoff: Duration,
+    /// Wait a random time between half and all of the backoff
+    pub jitter: bool,
+    /// Longest Retry-After that is honored. The request fails if the log
+    /// asks for a longer wait.
+    pub max_retry_after: Duration,
+    /// HTTP statuses to retry
+    pub retryable_statuses: Vec<u16>,
+    /// Retry requests that failed to connect
+    pub retry_connect_errors: bool,
+    /// Retry requests that timed out
+    pub retry_timeouts: bool,
+}
+
+// Statuses which tell a client the log couldn't have processed a request
+const SAFE_STATUSES: [u16; 2] = [429, 503];
+
+impl Default for RetryPolicy {
+    /// 4 attempts, backing off from 500ms, for rate limiting, server errors,
+    /// failed connections and timeouts
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+            max_retry_after: Duration::from_secs(120),
+            retryable_statuses: vec![429, 500, 502, 503, 504],
+            retry_connect_errors: true,
+            retry_timeouts: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy which never retries
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Whether a request which got status should be retried.
+    /// safe_to_repeat is false for requests that change the log.
+    pub fn retries_status(&self, status: u16, safe_to_repeat: bool) -> bool {
+        self.retryable_statuses.contains(&status)
+            && (safe_to_repeat || SAFE_STATUSES.contains(&status))
+    }
+
+    /// Whether a request which failed with error should be retried
+    pub fn retries_error(&self, error: &reqwest::Error, safe_to_repeat: bool) -> bool {
+        (self.retry_connect_errors && error.is_connect())
+            || (self.retry_timeouts && safe_to_repeat && error.is_timeout())
+    }
+
+    /// How long to wait after the given failed attempt, starting from 1.
+    /// retry_after is the log's Retry-After, if it sent one.
+    /// None means the log asked for longer than max_retry_after.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= self.max_retry_after).then_some(retry_after);
+        }
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        if !self.jitter {
+            return Some(backoff);
+        }
+        let mut random = [0; 4];
+        ring::rand::SystemRandom::new()
+            .fill(&mut random)
+            .expect("system randomness");
+        let fraction = u32::from_be_bytes(random) as f64 / u32::MAX as f64;
+        Some(backoff.mul_f64(0.5 + fraction / 2.0))
+    }
+
+    /// next_delay is how long to wait before retrying after the given
+    /// failed attempt, or None to give up
+    pub(crate) fn next_delay(
+        &self,
+        attempt: u32,
+        retry: bool,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if retry && attempt < self.max_attempts {
+            self.delay(attempt, retry_after)
+        } else {
+            None
+        }
+    }
+}
+
+/// retry_after parses a Retry-After header, which is either a number of
+/// seconds or an HTTP date
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+#[test]
+fn test_delay() {
+    let policy = RetryPolicy {
+        jitter: false,
+        max_backoff: Duration::from_secs(3),
+        ..RetryPolicy::default()
+    };
+    let delays: Vec<_> = (1..=5).map(|a| policy.delay(a, None)).collect();
+    assert_eq!(
+        delays,
+        [500, 1000, 2000, 3000, 3000].map(|ms| Some(Duration::from_millis(ms)))
+    );
+    assert_eq!(
+        policy.delay(1, Some(Duration::from_secs(60))),
+        Some(Duration::from_secs(60))
+    );
+    assert_eq!(policy.delay(1, Some(Duration::from_secs(600))), None);
+    assert_eq!(policy.delay(u32::MAX, None), Some(Duration::from_secs(3)));
+
+    let jittered = RetryPolicy::default();
+    for attempt in 1..=3 {
+        let delay = jittered.delay(attempt, None).expect("delays");
+        let backoff = Duration::from_millis(500 << (attempt - 1));
+        assert!(delay >= backoff / 2 && delay <= backoff, "{delay:?}");
+    }
+}
+
+#[test]
+fn test_retries_status() {
+    let policy = RetryPolicy::default();
+    assert!(policy.retries_status(503, true));
+    assert!(policy.retries_status(500, true));
+    assert!(!policy.retries_status(400, true));
+    // add-chain might have been processed by a log returning 500
+    assert!(!policy.retries_status(500, false));
+    assert!(policy.retries_status(429, false));
+    assert!(policy.retries_status(503, false));
+
+    let only_500 = RetryPolicy {
+        retryable_statuses: vec![500],
+        ..RetryPolicy::default()
+    };
+    assert!(!only_500.retries_status(503, true));
+}
+
+#[test]
+fn test_retry_after() {
+    let mut headers = reqwest::header::HeaderMap::new();
+    assert_eq!(retry_after(&headers), None);
+    headers.insert(reqwest::header::RETRY_AFTER, "7".parse().unwrap());
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+    headers.insert(
+        reqwest::header::RETRY_AFTER,
+        "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+    );
+    assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(100));
+    headers.insert(reqwest::header::RETRY_AFTER, later.parse().unwrap());
+    let delay = retry_after(&headers).expect("parses");
+    assert!(delay > Duration::from_secs(95) && delay <= Duration::from_secs(100));
+    headers.insert(reqwest::header::RETRY_AFTER, "soon".parse().unwrap());
+    assert_eq!(retry_after(&headers), None);
+}
@@ -0,0 +1,523 @@
+use thiserror::Error;
+use tls_codec::{Deserialize, Serialize, Size};
+use tls_codec_derive::{TlsDeserialize, TlsSerialize, TlsSize};
+
+use crate::entry::LogEntry;
+use crate::signature::{DigitallySigned, PublicKey, SignatureType};
+
+/// Errors verifying SCTs
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("unsupported SCT version {0}")]
+    UnsupportedVersion(u64),
+
+    #[error("SCT log ID doesn't match the log's key")]
+    WrongLogID,
+
+    #[error("SCT log ID is {0} bytes, not 32")]
+    BadLogIDLength(usize),
+
+    #[error("SCT extensions are {0} bytes, longer than allowed")]
+    ExtensionsTooLong(usize),
+
+    #[error("SCT list is {0} bytes, longer than allowed")]
+    ListTooLong(usize),
+
+    #[error("empty SCT list")]
+    EmptyList,
+
+    #[error("SCT list extension isn't an OCTET STRING")]
+    BadExtensionValue,
+
+    #[error("malformed SCT: {0}")]
+    TLS(#[from] tls_codec::Error),
+
+    #[error(transparent)]
+    Signature(#[from] crate::signature::Error),
+}
+
+/// CertificateTimestamp is the data signed in an SCT, described in S3.2.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
+struct CertificateTimestamp {
+    sct_version: u8,
+    signature_type: SignatureType,
+    timestamp: u64,
+    log_entry: LogEntry,
+    extensions: tls_codec::TlsByteVecU16,
+}
+
+/// verify checks an SCT for log_entry was issued by the log with public_key.
+/// The fields are those of an add-chain or add-pre-chain response.
+pub fn verify(
+    public_key: &PublicKey,
+    log_entry: &LogEntry,
+    sct_version: u64,
+    id: &[u8],
+    timestamp: u64,
+    extensions: &[u8],
+    signature: &[u8],
+) -> Result<(), Error> {
+    SignedCertificateTimestamp::from_fields(sct_version, id, timestamp, extensions, signature)?
+        .verify(public_key, log_entry)
+}
+
+/// SignedCertificateTimestamp is described in S3.2.
+/// This is the binary form found in certificates, OCSP responses and TLS
+/// extensions.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
+pub struct SignedCertificateTimestamp {
+    sct_version: u8,
+    id: [u8; 32],
+    timestamp: u64,
+    extensions: tls_codec::TlsByteVecU16,
+    signature: DigitallySigned,
+}
+
+impl SignedCertificateTimestamp {
+    pub fn new(
+        id: [u8; 32],
+        timestamp: u64,
+        extensions: Vec<u8>,
+        signature: DigitallySigned,
+    ) -> Result<SignedCertificateTimestamp, Error> {
+        if extensions.len() > u16::MAX as usize {
+            return Err(Error::ExtensionsTooLong(extensions.len()));
+        }
+        Ok(SignedCertificateTimestamp {
+            sct_version: crate::signature::V1,
+            id,
+            timestamp,
+            extensions: extensions.into(),
+            signature,
+        })
+    }
+
+    /// Build an SCT from the fields of an add-chain or add-pre-chain response
+    fn from_fields(
+        sct_version: u64,
+        id: &[u8],
+        timestamp: u64,
+        extensions: &[u8],
+        signature: &[u8],
+    ) -> Result<SignedCertificateTimestamp, Error> {
+        if sct_version != crate::signature::V1 as u64 {
+            return Err(Error::UnsupportedVersion(sct_version));
+        }
+        let id = id.try_into().map_err(|_| Error::BadLogIDLength(id.len()))?;
+        let signature = DigitallySigned::from_bytes(signature)?;
+        SignedCertificateTimestamp::new(id, timestamp, extensions.to_vec(), signature)
+    }
+
+    /// Decode a TLS encoded v1 SCT
+    pub fn from_bytes(data: &[u8]) -> Result<SignedCertificateTimestamp, Error> {
+        // Check the version first, as later versions may be laid out differently
+        if let Some(&version) = data.first() {
+            if version != crate::signature::V1 {
+                return Err(Error::UnsupportedVersion(version as u64));
+            }
+        }
+        Ok(SignedCertificateTimestamp::tls_deserialize_exact(data)?)
+    }
+
+    /// TLS encoding of this SCT
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.tls_serialize(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn version(&self) -> u8 {
+        self.sct_version
+    }
+
+    /// The log ID, the SHA-256 hash of the log's public key
+    pub fn log_id(&self) -> &[u8; 32] {
+        &self.id
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn extensions(&self) -> &[u8] {
+        self.extensions.as_slice()
+    }
+
+    pub fn signature(&self) -> &DigitallySigned {
+        &self.signature
+    }
+
+    /// verify checks this SCT was issued by the log with public_key for log_entry
+    pub fn verify(&self, public_key: &PublicKey, log_entry: &LogEntry) -> Result<(), Error> {
+        if self.id != public_key.log_id() {
+            return Err(Error::WrongLogID);
+        }
+        let signed = CertificateTimestamp {
+            sct_version: self.sct_version,
+            signature_type: SignatureType::CertificateTimestamp,
+            timestamp: self.timestamp,
+            log_entry: log_entry.clone(),
+            extensions: self.extensions.clone(),
+        };
+        let mut message = Vec::new();
+        signed
+            .tls_serialize(&mut message)
+            .map_err(crate::signature::Error::TLS)?;
+        Ok(public_key.verify(&message, &self.signature)?)
+    }
+}
+
+impl TryFrom<&crate::api::AddChainOutput> for SignedCertificateTimestamp {
+    type Error = Error;
+
+    fn try_from(sct: &crate::api::AddChainOutput) -> Result<Self, Self::Error> {
+        SignedCertificateTimestamp::from_fields(
+            sct.sct_version,
+            &sct.id,
+            sct.timestamp,
+            &sct.extensions,
+            &sct.signature,
+        )
+    }
+}
+
+impl TryFrom<&crate::api::AddPreChainOutput> for SignedCertificateTimestamp {
+    type Error = Error;
+
+    fn try_from(sct: &crate::api::AddPreChainOutput) -> Result<Self, Self::Error> {
+        SignedCertificateTimestamp::from_fields(
+            sct.sct_version,
+            &sct.id,
+            sct.timestamp,
+            &sct.extensions,
+            &sct.signature,
+        )
+    }
+}
+
+/// SignedCertificateTimestampList is described in S3.3.
+/// Each SCT is carried as its own length-prefixed TLS encoding.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SignedCertificateTimestampList {
+    scts: Vec<SignedCertificateTimestamp>,
+}
+
+impl SignedCertificateTimestampList {
+    pub fn new(scts: Vec<SignedCertificateTimestamp>) -> SignedCertificateTimestampList {
+        SignedCertificateTimestampList { scts }
+    }
+
+    pub fn scts(&self) -> &[SignedCertificateTimestamp] {
+        &self.scts
+    }
+
+    /// Decode a TLS encoded SignedCertificateTimestampList.
+    /// SCTs of later versions are skipped, but there must be at least one
+    /// v1 SCT.
+    pub fn from_bytes(data: &[u8]) -> Result<SignedCertificateTimestampList, Error> {
+        let list = tls_codec::TlsVecU16::<tls_codec::TlsByteVecU16>::tls_deserialize_exact(data)?;
+        let mut scts = Vec::with_capacity(list.len());
+        for sct in list.iter() {
+            match SignedCertificateTimestamp::from_bytes(sct.as_slice()) {
+                Ok(sct) => scts.push(sct),
+                Err(Error::UnsupportedVersion(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if scts.is_empty() {
+            return Err(Error::EmptyList);
+        }
+        Ok(SignedCertificateTimestampList { scts })
+    }
+
+    /// TLS encoding of this list
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.scts.is_empty() {
+            return Err(Error::EmptyList);
+        }
+        let mut list = Vec::with_capacity(self.scts.len());
+        for sct in &self.scts {
+            list.push(tls_codec::TlsByteVecU16::from(sct.to_bytes()?));
+        }
+        let list = tls_codec::TlsVecU16::from(list);
+        // tls_codec only debug_asserts on overlong vectors
+        let len = list.tls_serialized_len() - 2;
+        if len > u16::MAX as usize {
+            return Err(Error::ListTooLong(len));
+        }
+        let mut buf = Vec::new();
+        list.tls_serialize(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Decode the extension_data of a TLS signed_certificate_timestamp
+    /// extension, which is the TLS encoded list itself, S3.3.
+    pub fn from_tls_extension(
+        extension_data: &[u8],
+    ) -> Result<SignedCertificateTimestampList, Error> {
+        SignedCertificateTimestampList::from_bytes(extension_data)
+    }
+
+    /// Decode the extnValue of the X.509v3 SCT list extension, or of the
+    /// OCSP SCT list extension, S3.3.
+    pub fn from_extension_value(
+        extension_value: &[u8],
+    ) -> Result<SignedCertificateTimestampList, Error> {
+        let list = crate::der::Tlv::parse_tag(extension_value, crate::der::OCTET_STRING)
+            .map_err(|_| Error::BadExtensionValue)?;
+        SignedCertificateTimestampList::from_bytes(list.value)
+    }
+
+    /// The extnValue of the X.509v3 SCT list extension, 1.3.6.1.4.1.11129.2.4.2,
+    /// which is the TLS encoded list wrapped in a DER OCTET STRING, S3.3.
+    pub fn to_extension_value(&self) -> Result<Vec<u8>, Error> {
+        Ok(crate::der::encode(
+            crate::der::OCTET_STRING,
+            &[&self.to_bytes()?],
+        ))
+    }
+}
+
+impl crate::api::AddChainOutput {
+    /// verify checks this SCT was issued by the log with public_key for log_entry,
+    /// which can be built from the submitted chain with LogEntry::from_chain
+    pub fn verify(&self, public_key: &PublicKey, log_entry: &LogEntry) -> Result<(), Error> {
+        verify(
+            public_key,
+            log_entry,
+            self.sct_version,
+            &self.id,
+            self.timestamp,
+            &self.extensions,
+            &self.signature,
+        )
+    }
+}
+
+impl crate::api::AddPreChainOutput {
+    /// verify checks this SCT was issued by the log with public_key for log_entry,
+    /// which can be built from the submitted chain with LogEntry::from_pre_chain
+    pub fn verify(&self, public_key: &PublicKey, log_entry: &LogEntry) -> Result<(), Error> {
+        verify(
+            public_key,
+            log_entry,
+            self.sct_version,
+            &self.id,
+            self.timestamp,
+            &self.extensions,
+            &self.signature,
+        )
+    }
+}
+
+#[cfg(test)]
+/// test_sct signs an SCT for log_entry with a private key from testdata
+pub(crate) fn test_sct(
+    private_key_pem: &str,
+    public_key: &PublicKey,
+    log_entry: &LogEntry,
+    timestamp: u64,
+) -> crate::api::AddChainOutput {
+    let signed = CertificateTimestamp {
+        sct_version: crate::signature::V1,
+        signature_type: SignatureType::CertificateTimestamp,
+        timestamp,
+        log_entry: log_entry.clone(),
+        extensions: vec![].into(),
+    };
+    let mut message = Vec::new();
+    signed.tls_serialize(&mut message).expect("serializes");
+    crate::api::AddChainOutput {
+        sct_version: 0,
+        id: public_key.log_id().to_vec(),
+        timestamp,
+        extensions: vec![],
+        signature: crate::signature::test_sign(private_key_pem, &message),
+    }
+}
+
+#[test]
+fn test_verify_add_chain() {
+    use crate::signature::{TEST_EC_KEY, TEST_EC_PUB, TEST_RSA_PUB};
+
+    let key = PublicKey::from_pem(TEST_EC_PUB.as_bytes()).expect("parses");
+    let chain = vec![
+        include_bytes!("../testdata/cert.der").to_vec(),
+        include_bytes!("../testdata/ca.der").to_vec(),
+    ];
+    let entry = LogEntry::from_chain(&chain).expect("builds");
+    let sct = test_sct(TEST_EC_KEY, &key, &entry, 1767225600000);
+    sct.verify(&key, &entry).expect("verifies");
+
+    let mut wrong_time = sct.clone();
+    wrong_time.timestamp += 1;
+    assert!(matches!(
+        wrong_time.verify(&key, &entry),
+        Err(Error::Signature(crate::signature::Error::BadSignature))
+    ));
+
+    let mut wrong_version = sct.clone();
+    wrong_version.sct_version = 1;
+    assert!(matches!(
+        wrong_version.verify(&key, &entry),
+        Err(Error::UnsupportedVersion(1))
+    ));
+
+    let mut extensions = sct.clone();
+    extensions.extensions = vec![0];
+    assert!(matches!(
+        extensions.verify(&key, &entry),
+        Err(Error::Signature(crate::signature::Error::BadSignature))
+    ));
+
+    let other_key = PublicKey::from_pem(TEST_RSA_PUB.as_bytes()).expect("parses");
+    assert!(matches!(
+        sct.verify(&other_key, &entry),
+        Err(Error::WrongLogID)
+    ));
+
+    let precert_chain = vec![
+        include_bytes!("../testdata/precert.der").to_vec(),
+        include_bytes!("../testdata/ca.der").to_vec(),
+    ];
+    let precert_entry = LogEntry::from_pre_chain(&precert_chain).expect("builds");
+    assert!(matches!(
+        sct.verify(&key, &precert_entry),
+        Err(Error::Signature(crate::signature::Error::BadSignature))
+    ));
+}
+
+#[test]
+fn test_verify_add_pre_chain() {
+    use crate::signature::{TEST_RSA_KEY, TEST_RSA_PUB};
+
+    let key = PublicKey::from_pem(TEST_RSA_PUB.as_bytes()).expect("parses");
+    let chain = vec![
+        include_bytes!("../testdata/precert.der").to_vec(),
+        include_bytes!("../testdata/ca.der").to_vec(),
+    ];
+    let entry = LogEntry::from_pre_chain(&chain).expect("builds");
+    let sct = test_sct(TEST_RSA_KEY, &key, &entry, 1767225600000);
+    let sct = crate::api::AddPreChainOutput {
+        sct_version: sct.sct_version,
+        id: sct.id,
+        timestamp: sct.timestamp,
+        extensions: sct.extensions,
+        signature: sct.signature,
+    };
+    sct.verify(&key, &entry).expect("verifies");
+
+    // The SCT covers the issuer key hash
+    let other_issuer = vec![
+        include_bytes!("../testdata/precert.der").to_vec(),
+        include_bytes!("../testdata/precert.der").to_vec(),
+    ];
+    let other_entry = LogEntry::from_pre_chain(&other_issuer).expect("builds");
+    assert!(matches!(
+        sct.verify(&key, &other_entry),
+        Err(Error::Signature(crate::signature::Error::BadSignature))
+    ));
+}
+
+#[test]
+fn test_sct_list() {
+    use crate::signature::{TEST_EC_KEY, TEST_EC_PUB, TEST_RSA_KEY, TEST_RSA_PUB};
+
+    let chain = vec![
+        include_bytes!("../testdata/precert.der").to_vec(),
+        include_bytes!("../testdata/ca.der").to_vec(),
+    ];
+    let entry = LogEntry::from_pre_chain(&chain).expect("builds");
+    let ec_key = PublicKey::from_pem(TEST_EC_PUB.as_bytes()).expect("parses");
+    let rsa_key = PublicKey::from_pem(TEST_RSA_PUB.as_bytes()).expect("parses");
+    let ec_sct = test_sct(TEST_EC_KEY, &ec_key, &entry, 1767225600000);
+    let rsa_sct = test_sct(TEST_RSA_KEY, &rsa_key, &entry, 1767225600001);
+    let ec_sct = SignedCertificateTimestamp::try_from(&ec_sct).expect("converts");
+    let rsa_sct = SignedCertificateTimestamp::try_from(&rsa_sct).expect("converts");
+    assert_eq!(*ec_sct.log_id(), ec_key.log_id());
+    assert_eq!(rsa_sct.timestamp(), 1767225600001);
+    ec_sct.verify(&ec_key, &entry).expect("verifies");
+    rsa_sct.verify(&rsa_key, &entry).expect("verifies");
+
+    let encoded = ec_sct.to_bytes().expect("encodes");
+    // version, log ID, timestamp, empty extensions, then the DigitallySigned
+    assert_eq!(encoded[0], 0);
+    assert_eq!(&encoded[1..33], ec_key.log_id());
+    assert_eq!(encoded[33..41], 1767225600000u64.to_be_bytes());
+    assert_eq!(encoded[41..43], [0, 0]);
+    assert_eq!(encoded[43..45], [4, 3]);
+    assert_eq!(
+        SignedCertificateTimestamp::from_bytes(&encoded).expect("decodes"),
+        ec_sct
+    );
+    let mut v2 = encoded.clone();
+    v2[0] = 1;
+    assert!(matches!(
+        SignedCertificateTimestamp::from_bytes(&v2),
+        Err(Error::UnsupportedVersion(1))
+    ));
+
+    let list = SignedCertificateTimestampList::new(vec![ec_sct.clone(), rsa_sct.clone()]);
+    let list_bytes = list.to_bytes().expect("encodes");
+    let rsa_encoded = rsa_sct.to_bytes().expect("encodes");
+    let total = 2 + encoded.len() + 2 + rsa_encoded.len();
+    assert_eq!(list_bytes.len(), 2 + total);
+    assert_eq!(list_bytes[..2], (total as u16).to_be_bytes());
+    assert_eq!(list_bytes[2..4], (encoded.len() as u16).to_be_bytes());
+    assert_eq!(&list_bytes[4..4 + encoded.len()], &encoded[..]);
+    assert_eq!(
+        SignedCertificateTimestampList::from_bytes(&list_bytes).expect("decodes"),
+        list
+    );
+
+    let value = list.to_extension_value().expect("encodes");
+    let octets = crate::der::Tlv::parse_tag(&value, crate::der::OCTET_STRING).expect("parses");
+    assert_eq!(octets.value, &list_bytes[..]);
+    assert_eq!(
+        SignedCertificateTimestampList::from_extension_value(&value).expect("decodes"),
+        list
+    );
+    assert!(matches!(
+        SignedCertificateTimestampList::from_extension_value(&list_bytes),
+        Err(Error::BadExtensionValue)
+    ));
+    assert_eq!(
+        SignedCertificateTimestampList::from_tls_extension(&list_bytes).expect("decodes"),
+        list
+    );
+
+    assert!(matches!(
+        SignedCertificateTimestampList::from_bytes(&[0, 0]),
+        Err(Error::EmptyList)
+    ));
+    // SCTs of versions we don't know are skipped
+    let encode_list = |scts: &[&Vec<u8>]| {
+        let list: Vec<_> = scts
+            .iter()
+            .map(|sct| tls_codec::TlsByteVecU16::from(sct.as_slice()))
+            .collect();
+        tls_codec::TlsVecU16::from(list)
+            .tls_serialize_detached()
+            .expect("encodes")
+    };
+    assert_eq!(
+        SignedCertificateTimestampList::from_bytes(&encode_list(&[&v2, &encoded]))
+            .expect("decodes"),
+        SignedCertificateTimestampList::new(vec![ec_sct.clone()])
+    );
+    assert!(matches!(
+        SignedCertificateTimestampList::from_bytes(&encode_list(&[&v2])),
+        Err(Error::EmptyList)
+    ));
+    assert!(matches!(
+        SignedCertificateTimestampList::new(vec![]).to_bytes(),
+        Err(Error::EmptyList)
+    ));
+    assert!(matches!(
+        SignedCertificateTimestampList::from_bytes(&list_bytes[..list_bytes.len() - 1]),
+        Err(Error::TLS(_))
+    ));
+
+    let too_many = SignedCertificateTimestampList::new(vec![ec_sct; 1000]);
+    assert!(matches!(too_many.to_bytes(), Err(Error::ListTooLong(_))));
+}
@@ -0,0 +1,540 @@
+use base64::{engine::general_purpose::STANDARD as b64, Engine};
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, RSA_PKCS1_2048_8192_SHA256};
+use thiserror::Error;
+use tls_codec::{Deserialize, Serialize};
+use tls_codec_derive::{TlsDeserialize, TlsSerialize, TlsSize};
+
+use crate::der;
+
+/// Errors parsing keys and verifying signatures
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("malformed public key: {0}")]
+    BadKey(#[from] der::Error),
+
+    #[error("unsupported public key algorithm")]
+    UnsupportedKey,
+
+    #[error("no PUBLIC KEY block in PEM")]
+    NoPEMKey,
+
+    #[error("invalid base64 in PEM: {0}")]
+    PEMBase64(#[from] base64::DecodeError),
+
+    #[error("malformed DigitallySigned: {0}")]
+    TLS(#[from] tls_codec::Error),
+
+    #[error("unknown TLS HashAlgorithm {0}")]
+    UnknownHashAlgorithm(u8),
+
+    #[error("unknown TLS SignatureAlgorithm {0}")]
+    UnknownSignatureAlgorithm(u8),
+
+    #[error("signature algorithm {hash:?} with {signature:?} doesn't match the key")]
+    AlgorithmMismatch {
+        hash: HashAlgorithm,
+        signature: SignatureAlgorithm,
+    },
+
+    #[error("root hash is {0} bytes, expected 32")]
+    BadRootHash(usize),
+
+    #[error("signature verification failed")]
+    BadSignature,
+}
+
+/// KeyAlgorithm is the type of a log's public key.
+/// RFC 6962 S2.1.4 allows NIST P-256 ECDSA and RSA keys.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum KeyAlgorithm {
+    EcdsaP256,
+    Rsa,
+}
+
+impl KeyAlgorithm {
+    /// The algorithm a log with this kind of key signs with.
+    /// Logs always hash with SHA-256, S2.1.4.
+    pub fn signature_and_hash_algorithm(&self) -> SignatureAndHashAlgorithm {
+        let signature = match self {
+            KeyAlgorithm::EcdsaP256 => SignatureAlgorithm::Ecdsa,
+            KeyAlgorithm::Rsa => SignatureAlgorithm::Rsa,
+        };
+        SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::Sha256,
+            signature,
+        }
+    }
+}
+
+// id-ecPublicKey, 1.2.840.10045.2.1
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+// prime256v1, 1.2.840.10045.3.1.7
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+// rsaEncryption, 1.2.840.113549.1.1.1
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// PublicKey is a CT log's public key
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PublicKey {
+    algorithm: KeyAlgorithm,
+    // DER SubjectPublicKeyInfo
+    spki: Vec<u8>,
+    // The subjectPublicKey: an EC point or an RSAPublicKey
+    key: Vec<u8>,
+}
+
+impl PublicKey {
+    /// Parse a DER SubjectPublicKeyInfo
+    pub fn from_der(spki: &[u8]) -> Result<PublicKey, Error> {
+        let mut reader = der::Tlv::parse_tag(spki, der::SEQUENCE)?.reader();
+        let mut algorithm_id = reader.read_tag(der::SEQUENCE)?.reader();
+        let key = reader.read()?.bit_string()?;
+        reader.finish()?;
+
+        let algorithm_oid = algorithm_id.read_tag(der::OID)?.value;
+        let algorithm = if algorithm_oid == OID_EC_PUBLIC_KEY {
+            if algorithm_id.read_tag(der::OID)?.value != OID_P256 {
+                return Err(Error::UnsupportedKey);
+            }
+            KeyAlgorithm::EcdsaP256
+        } else if algorithm_oid == OID_RSA_ENCRYPTION {
+            // Parameters are NULL, but sometimes omitted
+            algorithm_id.read_optional(der::NULL)?;
+            KeyAlgorithm::Rsa
+        } else {
+            return Err(Error::UnsupportedKey);
+        };
+        algorithm_id.finish()?;
+
+        Ok(PublicKey {
+            algorithm,
+            spki: spki.to_vec(),
+            key: key.to_vec(),
+        })
+    }
+
+    /// Parse a PEM "PUBLIC KEY" block, as found in log lists and on log
+    /// operators' websites
+    pub fn from_pem(pem: &[u8]) -> Result<PublicKey, Error> {
+        const BEGIN: &str = "-----BEGIN PUBLIC KEY-----";
+        const END: &str = "-----END PUBLIC KEY-----";
+
+        let text = String::from_utf8_lossy(pem);
+        let start = text.find(BEGIN).ok_or(Error::NoPEMKey)? + BEGIN.len();
+        let end = start + text[start..].find(END).ok_or(Error::NoPEMKey)?;
+        let encoded: String = text[start..end]
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        PublicKey::from_der(&b64.decode(encoded)?)
+    }
+
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.algorithm
+    }
+
+    /// DER SubjectPublicKeyInfo
+    pub fn der(&self) -> &[u8] {
+        &self.spki
+    }
+
+    /// log_id is the SHA-256 hash of the key, as described in S3.2
+    pub fn log_id(&self) -> [u8; 32] {
+        ring::digest::digest(&ring::digest::SHA256, &self.spki)
+            .as_ref()
+            .try_into()
+            .expect("SHA-256 is 32 bytes")
+    }
+
+    /// verify checks a DigitallySigned over message was made by this key
+    pub fn verify(&self, message: &[u8], signed: &DigitallySigned) -> Result<(), Error> {
+        if signed.algorithm != self.algorithm.signature_and_hash_algorithm() {
+            return Err(Error::AlgorithmMismatch {
+                hash: signed.algorithm.hash,
+                signature: signed.algorithm.signature,
+            });
+        }
+
+        let key = match self.algorithm {
+            KeyAlgorithm::EcdsaP256 => UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &self.key),
+            KeyAlgorithm::Rsa => UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, &self.key),
+        };
+        key.verify(message, signed.signature())
+            .map_err(|_| Error::BadSignature)
+    }
+
+    /// verify_sth checks the tree_head_signature of a get-sth response
+    pub fn verify_sth(&self, sth: &crate::api::GetSthOutput) -> Result<(), Error> {
+        let signed = TreeHeadSignature {
+            version: V1,
+            signature_type: SignatureType::TreeHash,
+            timestamp: sth.timestamp,
+            tree_size: sth.tree_size,
+            sha256_root_hash: sth
+                .sha256_root_hash
+                .as_slice()
+                .try_into()
+                .map_err(|_| Error::BadRootHash(sth.sha256_root_hash.len()))?,
+        };
+        let mut message = Vec::new();
+        signed.tls_serialize(&mut message)?;
+        self.verify(&message, &sth.digitally_signed()?)
+    }
+}
+
+// Version v1 of S3.2
+pub(crate) const V1: u8 = 0;
+
+/// SignatureType is described in S3.2.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub(crate) enum SignatureType {
+    CertificateTimestamp = 0,
+    TreeHash = 1,
+}
+
+/// TreeHeadSignature is the data signed in an STH, described in S3.5.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
+struct TreeHeadSignature {
+    version: u8,
+    signature_type: SignatureType,
+    timestamp: u64,
+    tree_size: u64,
+    sha256_root_hash: [u8; 32],
+}
+
+/// HashAlgorithm is described in RFC 5246 S7.4.1.4.1.
+#[derive(TlsSerialize, TlsSize, PartialEq, Eq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum HashAlgorithm {
+    None = 0,
+    Md5 = 1,
+    Sha1 = 2,
+    Sha224 = 3,
+    Sha256 = 4,
+    Sha384 = 5,
+    Sha512 = 6,
+}
+
+impl TryFrom<u8> for HashAlgorithm {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => HashAlgorithm::None,
+            1 => HashAlgorithm::Md5,
+            2 => HashAlgorithm::Sha1,
+            3 => HashAlgorithm::Sha224,
+            4 => HashAlgorithm::Sha256,
+            5 => HashAlgorithm::Sha384,
+            6 => HashAlgorithm::Sha512,
+            _ => return Err(Error::UnknownHashAlgorithm(value)),
+        })
+    }
+}
+
+impl Deserialize for HashAlgorithm {
+    fn tls_deserialize<R: std::io::Read>(bytes: &mut R) -> Result<Self, tls_codec::Error> {
+        HashAlgorithm::try_from(u8::tls_deserialize(bytes)?)
+            .map_err(|e| tls_codec::Error::DecodingError(e.to_string()))
+    }
+}
+
+/// SignatureAlgorithm is described in RFC 5246 S7.4.1.4.1.
+#[derive(TlsSerialize, TlsSize, PartialEq, Eq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum SignatureAlgorithm {
+    Anonymous = 0,
+    Rsa = 1,
+    Dsa = 2,
+    Ecdsa = 3,
+}
+
+impl TryFrom<u8> for SignatureAlgorithm {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => SignatureAlgorithm::Anonymous,
+            1 => SignatureAlgorithm::Rsa,
+            2 => SignatureAlgorithm::Dsa,
+            3 => SignatureAlgorithm::Ecdsa,
+            _ => return Err(Error::UnknownSignatureAlgorithm(value)),
+        })
+    }
+}
+
+impl Deserialize for SignatureAlgorithm {
+    fn tls_deserialize<R: std::io::Read>(bytes: &mut R) -> Result<Self, tls_codec::Error> {
+        SignatureAlgorithm::try_from(u8::tls_deserialize(bytes)?)
+            .map_err(|e| tls_codec::Error::DecodingError(e.to_string()))
+    }
+}
+
+/// SignatureAndHashAlgorithm is described in RFC 5246 S7.4.1.4.1.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SignatureAndHashAlgorithm {
+    pub hash: HashAlgorithm,
+    pub signature: SignatureAlgorithm,
+}
+
+/// DigitallySigned is described in RFC 5246 S4.7.
+/// It's the format of STH signatures and SCT signatures.
+#[derive(TlsSerialize, TlsDeserialize, TlsSize, PartialEq, Debug, Clone)]
+pub struct DigitallySigned {
+    algorithm: SignatureAndHashAlgorithm,
+    signature: tls_codec::TlsByteVecU16,
+}
+
+impl DigitallySigned {
+    pub fn new(algorithm: SignatureAndHashAlgorithm, signature: Vec<u8>) -> DigitallySigned {
+        DigitallySigned {
+            algorithm,
+            signature: signature.into(),
+        }
+    }
+
+    /// Decode a TLS encoded DigitallySigned, such as the signature field of
+    /// an add-chain response or the tree_head_signature of an STH
+    pub fn from_bytes(data: &[u8]) -> Result<DigitallySigned, Error> {
+        // Check the algorithms up front, to report unknown ones by name
+        if let [hash, signature, ..] = data {
+            HashAlgorithm::try_from(*hash)?;
+            SignatureAlgorithm::try_from(*signature)?;
+        }
+        Ok(DigitallySigned::tls_deserialize_exact(data)?)
+    }
+
+    /// TLS encoding of this DigitallySigned
+    pub fn to_bytes(&self) -> Result<Vec<u8>, tls_codec::Error> {
+        let mut buf = Vec::new();
+        self.tls_serialize(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn algorithm(&self) -> SignatureAndHashAlgorithm {
+        self.algorithm
+    }
+
+    /// The signature: DER ECDSA-Sig-Value, or RSASSA-PKCS1-v1_5
+    pub fn signature(&self) -> &[u8] {
+        self.signature.as_slice()
+    }
+}
+
+#[cfg(test)]
+/// test_sign makes a TLS encoded DigitallySigned over message with a
+/// private key from testdata
+pub(crate) fn test_sign(private_key_pem: &str, message: &[u8]) -> Vec<u8> {
+    use ring::signature::{EcdsaKeyPair, RsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    let encoded: String = private_key_pem
+        .lines()
+        .filter(|l| !l.starts_with("-----"))
+        .collect();
+    let pkcs8 = b64.decode(encoded).expect("base64");
+    let rng = ring::rand::SystemRandom::new();
+    let (key_algorithm, signature) =
+        match EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8, &rng) {
+            Ok(key) => (
+                KeyAlgorithm::EcdsaP256,
+                key.sign(&rng, message).expect("signs").as_ref().to_vec(),
+            ),
+            Err(_) => {
+                let key = RsaKeyPair::from_pkcs8(&pkcs8).expect("EC or RSA key");
+                let mut signature = vec![0; key.public().modulus_len()];
+                key.sign(
+                    &ring::signature::RSA_PKCS1_SHA256,
+                    &rng,
+                    message,
+                    &mut signature,
+                )
+                .expect("signs");
+                (KeyAlgorithm::Rsa, signature)
+            }
+        };
+    DigitallySigned::new(key_algorithm.signature_and_hash_algorithm(), signature)
+        .to_bytes()
+        .expect("serializes")
+}
+
+#[cfg(test)]
+/// test_sth builds an STH signed with a private key from testdata
+pub(crate) fn test_sth(
+    private_key_pem: &str,
+    tree_size: u64,
+    timestamp: u64,
+    sha256_root_hash: [u8; 32],
+) -> crate::api::GetSthOutput {
+    let signed = TreeHeadSignature {
+        version: V1,
+        signature_type: SignatureType::TreeHash,
+        timestamp,
+        tree_size,
+        sha256_root_hash,
+    };
+    let mut message = Vec::new();
+    signed.tls_serialize(&mut message).expect("serializes");
+    crate::api::GetSthOutput {
+        tree_size,
+        timestamp,
+        sha256_root_hash: sha256_root_hash.to_vec(),
+        tree_head_signature: test_sign(private_key_pem, &message),
+    }
+}
+
+#[cfg(test)]
+pub(crate) const TEST_EC_KEY: &str = include_str!("../testdata/log-ec.key");
+#[cfg(test)]
+pub(crate) const TEST_EC_PUB: &str = include_str!("../testdata/log-ec.pub");
+#[cfg(test)]
+pub(crate) const TEST_RSA_KEY: &str = include_str!("../testdata/log-rsa.key");
+#[cfg(test)]
+pub(crate) const TEST_RSA_PUB: &str = include_str!("../testdata/log-rsa.pub");
+
+#[test]
+fn test_parse_keys() {
+    let ec = PublicKey::from_pem(TEST_EC_PUB.as_bytes()).expect("parses");
+    assert_eq!(ec.algorithm(), KeyAlgorithm::EcdsaP256);
+    assert_eq!(ec.der().len(), 91);
+    assert_eq!(PublicKey::from_der(ec.der()).expect("parses"), ec);
+
+    let rsa = PublicKey::from_pem(TEST_RSA_PUB.as_bytes()).expect("parses");
+    assert_eq!(rsa.algorithm(), KeyAlgorithm::Rsa);
+    assert_ne!(rsa.log_id(), ec.log_id());
+
+    assert!(matches!(
+        PublicKey::from_pem(TEST_EC_KEY.as_bytes()),
+        Err(Error::NoPEMKey)
+    ));
+    assert!(matches!(
+        PublicKey::from_der(&ec.der()[..90]),
+        Err(Error::BadKey(_))
+    ));
+
+    // An Ed25519 key
+    let ed25519 = &[
+        0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00, 0x19, 0xbf, 0x44,
+        0x09, 0x69, 0x84, 0xcd, 0xfe, 0x85, 0x41, 0xba, 0xc1, 0x67, 0xdc, 0x3b, 0x96, 0xc8, 0x50,
+        0x86, 0xaa, 0x30, 0xb6, 0xb6, 0xcb, 0x0c, 0x5c, 0x38, 0xad, 0x70, 0x31, 0x66, 0xe1,
+    ];
+    assert!(matches!(
+        PublicKey::from_der(ed25519),
+        Err(Error::UnsupportedKey)
+    ));
+}
+
+#[test]
+fn test_verify_sth() {
+    for (private, public) in [(TEST_EC_KEY, TEST_EC_PUB), (TEST_RSA_KEY, TEST_RSA_PUB)] {
+        let key = PublicKey::from_pem(public.as_bytes()).expect("parses");
+        let mut sth = crate::api::GetSthOutput {
+            tree_size: 319918523,
+            timestamp: 1693958873214,
+            sha256_root_hash: vec![0x5a; 32],
+            tree_head_signature: vec![],
+        };
+        let signed = TreeHeadSignature {
+            version: V1,
+            signature_type: SignatureType::TreeHash,
+            timestamp: sth.timestamp,
+            tree_size: sth.tree_size,
+            sha256_root_hash: [0x5a; 32],
+        };
+        let mut message = Vec::new();
+        signed.tls_serialize(&mut message).expect("serializes");
+        assert_eq!(message.len(), 1 + 1 + 8 + 8 + 32);
+        sth.tree_head_signature = test_sign(private, &message);
+
+        key.verify_sth(&sth).expect("verifies");
+
+        let mut wrong_size = sth.clone();
+        wrong_size.tree_size += 1;
+        assert!(matches!(
+            key.verify_sth(&wrong_size),
+            Err(Error::BadSignature)
+        ));
+
+        let mut wrong_hash = sth.clone();
+        wrong_hash.tree_head_signature[0] = 2; // sha1
+        assert!(matches!(
+            key.verify_sth(&wrong_hash),
+            Err(Error::AlgorithmMismatch {
+                hash: HashAlgorithm::Sha1,
+                ..
+            })
+        ));
+
+        let mut truncated = sth.clone();
+        truncated.tree_head_signature.pop();
+        assert!(matches!(key.verify_sth(&truncated), Err(Error::TLS(_))));
+
+        let mut short_root = sth.clone();
+        short_root.sha256_root_hash.pop();
+        assert!(matches!(
+            key.verify_sth(&short_root),
+            Err(Error::BadRootHash(31))
+        ));
+    }
+
+    // Signed by the EC key, checked with the RSA key
+    let ec_signed =
+        DigitallySigned::from_bytes(&test_sign(TEST_EC_KEY, b"message")).expect("decodes");
+    let rsa = PublicKey::from_pem(TEST_RSA_PUB.as_bytes()).expect("parses");
+    assert!(matches!(
+        rsa.verify(b"message", &ec_signed),
+        Err(Error::AlgorithmMismatch {
+            hash: HashAlgorithm::Sha256,
+            signature: SignatureAlgorithm::Ecdsa
+        })
+    ));
+}
+
+#[test]
+fn test_digitally_signed() {
+    let data = &[
+        0x04, // sha256
+        0x03, // ecdsa
+        0x00, 0x03, // 16 bit length
+        0x30, 0x01, 0x00, // "signature"
+    ];
+    let signed = DigitallySigned::from_bytes(data).expect("decodes");
+    assert_eq!(
+        signed.algorithm(),
+        SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::Sha256,
+            signature: SignatureAlgorithm::Ecdsa
+        }
+    );
+    assert_eq!(signed.signature(), &[0x30, 0x01, 0x00]);
+    assert_eq!(signed.to_bytes().expect("encodes"), data);
+
+    let mut unknown_hash = data.to_vec();
+    unknown_hash[0] = 7;
+    assert!(matches!(
+        DigitallySigned::from_bytes(&unknown_hash),
+        Err(Error::UnknownHashAlgorithm(7))
+    ));
+
+    let mut unknown_signature = data.to_vec();
+    unknown_signature[1] = 64;
+    assert!(matches!(
+        DigitallySigned::from_bytes(&unknown_signature),
+        Err(Error::UnknownSignatureAlgorithm(64))
+    ));
+    // Nested in other structures, the error is still descriptive
+    assert!(matches!(
+        SignatureAlgorithm::tls_deserialize(&mut &unknown_signature[1..]),
+        Err(tls_codec::Error::DecodingError(message))
+            if message.contains("SignatureAlgorithm 64")
+    ));
+
+    assert!(matches!(
+        DigitallySigned::from_bytes(&data[..5]),
+        Err(Error::TLS(_))
+    ));
+    assert!(matches!(
+        DigitallySigned::from_bytes(&[data, &[0][..]].concat()),
+        Err(Error::TLS(_))
+    ));
+}
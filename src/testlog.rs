@@ -0,0 +1,201 @@
+// A minimal HTTP/1.1 server for testing the client against a fake log.
+// Each connection serves one request, and is closed after the response.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD as b64, Engine};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Request is a request received by the test server
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// Path without the query string
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Headers, with lowercase names
+    pub headers: HashMap<String, String>,
+}
+
+/// Response is what the test server should reply with
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(body: serde_json::Value) -> Response {
+        Response {
+            status: 200,
+            headers: vec![],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn status(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            headers: vec![],
+            body: body.to_string(),
+        }
+    }
+}
+
+impl Request {
+    /// A numeric query parameter
+    pub fn param(&self, name: &str) -> u64 {
+        self.query[name].parse().expect("numeric parameter")
+    }
+}
+
+/// serve starts a server calling handler for every request, and returns
+/// its base URL
+pub async fn serve<F>(handler: F) -> String
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("binds");
+    let addr = listener.local_addr().expect("has address");
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let request = match read_request(&mut stream).await {
+                    Some(request) => request,
+                    None => return,
+                };
+                let response = handler(&request);
+                let mut out = format!(
+                    "HTTP/1.1 {} Test\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n",
+                    response.status,
+                    response.body.len()
+                );
+                for (name, value) in &response.headers {
+                    out.push_str(&format!("{name}: {value}\r\n"));
+                }
+                out.push_str("\r\n");
+                out.push_str(&response.body);
+                let _ = stream.write_all(out.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            });
+        }
+    });
+    format!("http://{addr}/")
+}
+
+async fn read_request(stream: &mut tokio::net::TcpStream) -> Option<Request> {
+    let mut buf = Vec::new();
+    let header_end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        let mut chunk = [0; 1024];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let target = lines.next()?.split(' ').nth(1)?;
+    let headers: HashMap<String, String> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let content_length = headers
+        .get("content-length")
+        .map(|value| value.parse().unwrap_or(0))
+        .unwrap_or(0);
+
+    // The body is read, but not needed by any test
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let mut chunk = [0; 1024];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    Some(Request {
+        path: path.to_string(),
+        query,
+        headers,
+    })
+}
+
+/// leaf_input of the test log's entry at index, an x509_entry of cert.der
+/// with index as the timestamp
+pub fn leaf_input(index: u64) -> Vec<u8> {
+    let cert = include_bytes!("../testdata/cert.der").to_vec();
+    crate::entry::MerkleTreeLeaf::new(
+        index,
+        crate::entry::LogEntry::X509Entry(crate::entry::Asn1Cert::new(cert)),
+        vec![],
+    )
+    .to_bytes()
+    .expect("encodes")
+}
+
+/// get_entries answers a get-entries request for a log of tree_size
+/// entries, returning at most batch_size entries
+pub fn get_entries(request: &Request, tree_size: u64, batch_size: u64) -> Response {
+    let start = request.param("start");
+    let end = request.param("end").min(tree_size - 1);
+    if start > end {
+        return Response::status(400, "bad range");
+    }
+    let end = end.min(start + batch_size - 1);
+    let entries: Vec<_> = (start..=end)
+        .map(|i| {
+            serde_json::json!({
+                "leaf_input": b64.encode(leaf_input(i)),
+                "extra_data": b64.encode(include_bytes!("../testdata/ca.der")),
+            })
+        })
+        .collect();
+    Response::json(serde_json::json!({ "entries": entries }))
+}
+
+/// leaf_hashes of the first tree_size entries of the test log
+pub fn leaf_hashes(tree_size: u64) -> Vec<crate::merkle::Hash> {
+    (0..tree_size)
+        .map(|i| crate::merkle::leaf_data_hash(&leaf_input(i)))
+        .collect()
+}
+
+/// sth of the test log at tree_size, signed with the test EC log key
+pub fn sth(tree_size: u64) -> crate::api::GetSthOutput {
+    crate::signature::test_sth(
+        crate::signature::TEST_EC_KEY,
+        tree_size,
+        1767225600000 + tree_size,
+        crate::merkle::tree_hash_from_leaf_hashes(&leaf_hashes(tree_size)),
+    )
+}
+
+/// get_sth_consistency answers a get-sth-consistency request
+pub fn get_sth_consistency(request: &Request) -> Response {
+    let first = request.param("first");
+    let second = request.param("second");
+    let proof = crate::merkle::reference_consistency(first as usize, &leaf_hashes(second));
+    let proof: Vec<_> = proof.iter().map(|h| b64.encode(h)).collect();
+    Response::json(serde_json::json!({ "consistency": proof }))
+}
+
+impl Response {
+    /// JSON of an STH
+    pub fn sth(sth: &crate::api::GetSthOutput) -> Response {
+        Response::json(serde_json::to_value(sth).expect("serializes"))
+    }
+}
@@ -0,0 +1,249 @@
+// Just enough X.509 to build CT log entries from certificates.
+// Fields are kept as their original encodings, so a TBSCertificate can be
+// rebuilt with extensions removed without disturbing anything else.
+use crate::der::{self, Tlv};
+
+// CT precertificate poison, 1.3.6.1.4.1.11129.2.4.3
+pub const OID_CT_POISON: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x03];
+// Embedded SCT list, 1.3.6.1.4.1.11129.2.4.2
+pub const OID_CT_SCT_LIST: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x02];
+// Precertificate Signing Certificate EKU, 1.3.6.1.4.1.11129.2.4.4
+pub const OID_CT_PRECERT_SIGNING: &[u8] =
+    &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x04];
+// id-ce-authorityKeyIdentifier, 2.5.29.35
+pub const OID_AUTHORITY_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x23];
+// id-ce-extKeyUsage, 2.5.29.37
+pub const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+
+/// TbsCertificate is a parsed TBSCertificate from RFC 5280 S4.1
+#[derive(Debug, Clone)]
+pub struct TbsCertificate<'a> {
+    version: Option<&'a [u8]>,
+    serial_number: &'a [u8],
+    signature: &'a [u8],
+    issuer: &'a [u8],
+    validity: &'a [u8],
+    subject: &'a [u8],
+    subject_public_key_info: &'a [u8],
+    issuer_unique_id: Option<&'a [u8]>,
+    subject_unique_id: Option<&'a [u8]>,
+    extensions: Vec<Extension<'a>>,
+}
+
+/// Extension is a single certificate extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extension<'a> {
+    pub oid: &'a [u8],
+    pub critical: bool,
+    /// Contents of the extnValue OCTET STRING
+    pub value: &'a [u8],
+    /// The whole encoded Extension
+    pub raw: &'a [u8],
+}
+
+/// tbs_certificate returns the encoded TBSCertificate of a DER certificate
+pub fn tbs_certificate(cert: &[u8]) -> Result<&[u8], der::Error> {
+    let mut reader = Tlv::parse_tag(cert, der::SEQUENCE)?.reader();
+    let tbs = reader.read_tag(der::SEQUENCE)?;
+    reader.read_tag(der::SEQUENCE)?; // signatureAlgorithm
+    reader.read_tag(der::BIT_STRING)?; // signatureValue
+    reader.finish()?;
+    Ok(tbs.raw)
+}
+
+/// issuer_key_hash is the SHA-256 hash of the issuer's DER
+/// SubjectPublicKeyInfo, used in a PreCert, S3.2.
+pub fn issuer_key_hash(issuer: &[u8]) -> Result<[u8; 32], der::Error> {
+    let issuer = TbsCertificate::from_certificate(issuer)?;
+    Ok(
+        ring::digest::digest(&ring::digest::SHA256, issuer.subject_public_key_info())
+            .as_ref()
+            .try_into()
+            .expect("SHA-256 is 32 bytes"),
+    )
+}
+
+impl<'a> TbsCertificate<'a> {
+    /// Parse a DER TBSCertificate
+    pub fn parse(tbs: &'a [u8]) -> Result<TbsCertificate<'a>, der::Error> {
+        let mut reader = Tlv::parse_tag(tbs, der::SEQUENCE)?.reader();
+        let version = reader.read_optional(der::context(0))?.map(|t| t.raw);
+        let serial_number = reader.read_tag(der::INTEGER)?.raw;
+        let signature = reader.read_tag(der::SEQUENCE)?.raw;
+        let issuer = reader.read_tag(der::SEQUENCE)?.raw;
+        let validity = reader.read_tag(der::SEQUENCE)?.raw;
+        let subject = reader.read_tag(der::SEQUENCE)?.raw;
+        let subject_public_key_info = reader.read_tag(der::SEQUENCE)?.raw;
+        let issuer_unique_id = read_unique_id(&mut reader, 1)?;
+        let subject_unique_id = read_unique_id(&mut reader, 2)?;
+        let extensions = match reader.read_optional(der::context(3))? {
+            Some(extensions) => parse_extensions(&extensions)?,
+            None => vec![],
+        };
+        reader.finish()?;
+
+        Ok(TbsCertificate {
+            version,
+            serial_number,
+            signature,
+            issuer,
+            validity,
+            subject,
+            subject_public_key_info,
+            issuer_unique_id,
+            subject_unique_id,
+            extensions,
+        })
+    }
+
+    /// Parse the TBSCertificate of a DER certificate
+    pub fn from_certificate(cert: &'a [u8]) -> Result<TbsCertificate<'a>, der::Error> {
+        TbsCertificate::parse(tbs_certificate(cert)?)
+    }
+
+    /// DER SubjectPublicKeyInfo
+    pub fn subject_public_key_info(&self) -> &'a [u8] {
+        self.subject_public_key_info
+    }
+
+    /// DER issuer Name
+    pub fn issuer(&self) -> &'a [u8] {
+        self.issuer
+    }
+
+    /// Replace the issuer with another DER Name
+    pub fn set_issuer(&mut self, issuer: &'a [u8]) {
+        self.issuer = issuer;
+    }
+
+    /// The extension with the given OID, if present
+    pub fn extension(&self, oid: &[u8]) -> Option<&Extension<'a>> {
+        self.extensions.iter().find(|e| e.oid == oid)
+    }
+
+    /// Replace the extension with the same OID as extension, keeping its
+    /// position. Returns false if there was no such extension.
+    pub fn replace_extension(&mut self, extension: Extension<'a>) -> bool {
+        match self.extensions.iter_mut().find(|e| e.oid == extension.oid) {
+            Some(e) => {
+                *e = extension;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the extension with the given OID, returning it if it was present
+    pub fn remove_extension(&mut self, oid: &[u8]) -> Option<Extension<'a>> {
+        let index = self.extensions.iter().position(|e| e.oid == oid)?;
+        Some(self.extensions.remove(index))
+    }
+
+    /// DER encoding of this TBSCertificate.
+    /// The extensions field is left out if there are no extensions.
+    pub fn encode(&self) -> Vec<u8> {
+        let extensions: Vec<&[u8]> = self.extensions.iter().map(|e| e.raw).collect();
+        let extensions = der::encode(der::SEQUENCE, &extensions);
+        let extensions = der::encode(der::context(3), &[&extensions]);
+
+        let mut fields: Vec<&[u8]> = vec![];
+        fields.extend(self.version);
+        fields.extend([
+            self.serial_number,
+            self.signature,
+            self.issuer,
+            self.validity,
+            self.subject,
+            self.subject_public_key_info,
+        ]);
+        fields.extend(self.issuer_unique_id);
+        fields.extend(self.subject_unique_id);
+        if !self.extensions.is_empty() {
+            fields.push(&extensions);
+        }
+        der::encode(der::SEQUENCE, &fields)
+    }
+}
+
+/// issuerUniqueID and subjectUniqueID are IMPLICIT BIT STRINGs, which may use
+/// the primitive or constructed form
+fn read_unique_id<'a>(reader: &mut der::Reader<'a>, n: u8) -> Result<Option<&'a [u8]>, der::Error> {
+    match reader.peek_tag() {
+        Some(tag) if tag == 0x80 | n || tag == der::context(n) => Ok(Some(reader.read()?.raw)),
+        _ => Ok(None),
+    }
+}
+
+/// parse_extensions reads an EXPLICIT tagged Extensions field, as found in
+/// certificates and OCSP responses
+pub fn parse_extensions<'a>(extensions: &Tlv<'a>) -> Result<Vec<Extension<'a>>, der::Error> {
+    let mut outer = extensions.reader();
+    let mut reader = outer.read_tag(der::SEQUENCE)?.reader();
+    outer.finish()?;
+
+    let mut parsed = vec![];
+    while reader.peek_tag().is_some() {
+        let extension = reader.read_tag(der::SEQUENCE)?;
+        let mut fields = extension.reader();
+        let oid = fields.read_tag(der::OID)?.value;
+        let critical = match fields.read_optional(der::BOOLEAN)? {
+            Some(b) => b.value != [0],
+            None => false,
+        };
+        let value = fields.read_tag(der::OCTET_STRING)?.value;
+        fields.finish()?;
+        parsed.push(Extension {
+            oid,
+            critical,
+            value,
+            raw: extension.raw,
+        });
+    }
+    Ok(parsed)
+}
+
+#[test]
+fn test_tbs_roundtrip() {
+    for cert in [
+        &include_bytes!("../testdata/ca.der")[..],
+        &include_bytes!("../testdata/cert.der")[..],
+        &include_bytes!("../testdata/precert.der")[..],
+    ] {
+        let raw = tbs_certificate(cert).expect("parses");
+        let tbs = TbsCertificate::parse(raw).expect("parses");
+        assert_eq!(tbs.encode(), raw);
+        assert!(tbs.extensions.len() >= 2);
+    }
+}
+
+#[test]
+fn test_remove_extension() {
+    let precert = include_bytes!("../testdata/precert.der");
+    let raw = tbs_certificate(precert).expect("parses");
+    let mut tbs = TbsCertificate::parse(raw).expect("parses");
+
+    let poison = *tbs
+        .extensions
+        .iter()
+        .find(|e| e.oid == OID_CT_POISON)
+        .expect("has poison");
+    assert!(poison.critical);
+    assert_eq!(poison.value, &[der::NULL, 0]);
+    assert_eq!(tbs.remove_extension(OID_CT_POISON), Some(poison));
+    assert_eq!(tbs.remove_extension(OID_CT_POISON), None);
+
+    let without_poison = tbs.encode();
+    assert_eq!(without_poison.len(), raw.len() - poison.raw.len());
+    let reparsed = TbsCertificate::parse(&without_poison).expect("parses");
+    assert_eq!(reparsed.extensions, tbs.extensions);
+
+    // With no extensions left, the extensions field is omitted entirely
+    while let Some(oid) = tbs.extensions.first().map(|e| e.oid) {
+        tbs.remove_extension(oid);
+    }
+    let no_extensions = tbs.encode();
+    assert!(TbsCertificate::parse(&no_extensions)
+        .expect("parses")
+        .extensions
+        .is_empty());
+}